@@ -0,0 +1,173 @@
+use crate::error::{Result, RfeError};
+use crate::source::GitUrl;
+use git2::Repository;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// File inside each cache entry recording when it was last used
+const LAST_USED_FILE: &str = "rfe-last-used";
+
+/// On-disk cache of bare clones of template repositories
+#[derive(Debug, Clone)]
+pub struct Cache {
+    root: PathBuf,
+}
+
+/// A repository stored in the cache
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub key: String,
+    pub url: Option<String>,
+    pub path: PathBuf,
+    pub last_used: Option<SystemTime>,
+    pub size: u64,
+}
+
+impl Cache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Cache { root: root.into() }
+    }
+
+    /// The cache in `$RFE_CACHE_DIR`, `$XDG_CACHE_HOME/rfe` or `~/.cache/rfe`
+    pub fn from_env() -> Option<Self> {
+        let non_empty = |name| env::var_os(name).filter(|value| !value.is_empty());
+        if let Some(dir) = non_empty("RFE_CACHE_DIR") {
+            return Some(Cache::new(dir));
+        }
+        let base = non_empty("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(Cache::new(base.join("rfe")))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Directory holding the bare repository for `url`
+    pub fn path_for(&self, url: &str) -> PathBuf {
+        self.root.join(Self::key(url))
+    }
+
+    /// Cache key for `url`, shared by every spelling of the same repository
+    ///
+    /// The transport, credentials and a trailing `.git` are ignored and the
+    /// host is lowercased; the result is percent-encoded so it is safe to use
+    /// as a directory name.
+    pub fn key(url: &str) -> String {
+        let normalized = match GitUrl::parse(url) {
+            Some(git_url) => git_url.normalized(),
+            None => {
+                let trimmed = url.trim_end_matches('/');
+                trimmed.strip_suffix(".git").unwrap_or(trimmed).to_string()
+            }
+        };
+        url::form_urlencoded::byte_serialize(normalized.as_bytes()).collect()
+    }
+
+    /// Record that the entry at `path` was just used
+    pub fn touch(&self, path: &Path) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let marker = path.join(LAST_USED_FILE);
+        fs::write(&marker, now.to_string()).map_err(|e| RfeError::io(&marker, e))
+    }
+
+    /// Every repository currently in the cache, sorted by key
+    ///
+    /// Only directories named like a cache key that hold a bare repository
+    /// count; anything else in the cache directory is left alone.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+        let dir = fs::read_dir(&self.root).map_err(|e| RfeError::io(&self.root, e))?;
+
+        let mut entries = Vec::new();
+        for entry in dir {
+            let path = entry.map_err(|e| RfeError::io(&self.root, e))?.path();
+            let key = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) if path.is_dir() && is_key(name) => name.to_string(),
+                _ => continue,
+            };
+            let repo = match Repository::open_bare(&path) {
+                Ok(repo) => repo,
+                Err(_) => continue,
+            };
+            let url = repo
+                .find_remote("origin")
+                .ok()
+                .and_then(|remote| remote.url().map(String::from));
+            let last_used = fs::read_to_string(path.join(LAST_USED_FILE))
+                .ok()
+                .and_then(|secs| secs.trim().parse().ok())
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+            entries.push(CacheEntry {
+                key,
+                url,
+                size: dir_size(&path)?,
+                last_used,
+                path,
+            });
+        }
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(entries)
+    }
+
+    /// Remove entries that have not been used for longer than `max_age`
+    ///
+    /// Entries without a last-used time are kept: a clone in progress has
+    /// not recorded one yet. Returns the entries that were removed.
+    pub fn prune(&self, max_age: Duration) -> Result<Vec<CacheEntry>> {
+        let now = SystemTime::now();
+        let mut removed = Vec::new();
+        for entry in self.entries()? {
+            let stale = match entry.last_used {
+                Some(last_used) => now.duration_since(last_used).unwrap_or_default() > max_age,
+                None => false,
+            };
+            if stale {
+                fs::remove_dir_all(&entry.path).map_err(|e| RfeError::io(&entry.path, e))?;
+                removed.push(entry);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Remove every entry, returning how many were removed
+    pub fn clear(&self) -> Result<usize> {
+        let entries = self.entries()?;
+        for entry in &entries {
+            fs::remove_dir_all(&entry.path).map_err(|e| RfeError::io(&entry.path, e))?;
+        }
+        Ok(entries.len())
+    }
+}
+
+/// Whether `name` could have been produced by `Cache::key`
+fn is_key(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"*-._+%".contains(&b))
+}
+
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path).map_err(|e| RfeError::io(path, e))? {
+        let entry = entry.map_err(|e| RfeError::io(path, e))?;
+        let metadata = entry
+            .metadata()
+            .map_err(|e| RfeError::io(entry.path(), e))?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
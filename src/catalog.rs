@@ -0,0 +1,128 @@
+use crate::error::Result;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::scaffold::SCAFFOLD_FILES;
+use crate::stuff::SourceContentReader;
+use serde::Serialize;
+
+/// What a source offers: the templates its manifests describe and every file
+/// in it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Catalog {
+    pub templates: Vec<TemplateEntry>,
+    /// Every file of the source, relative to its root
+    pub files: Vec<String>,
+}
+
+/// A template in a source, described by its `rfe.toml`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TemplateEntry {
+    /// Directory of the template within the source, `None` at its root
+    pub subdir: Option<String>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub files: Vec<TemplateFile>,
+    /// Why the template is unavailable, when its `rfe.toml` cannot be read
+    /// or parsed
+    pub error: Option<String>,
+}
+
+/// A file a template renders
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateFile {
+    /// Path of the file within the template
+    pub source: String,
+    /// Path it is written to, relative to the target
+    pub destination: String,
+    pub description: Option<String>,
+    /// Whether the source lacks the file, so the embedded scaffold's copy
+    /// is used
+    pub embedded: bool,
+}
+
+impl Catalog {
+    /// Walk the source `reader` reads from
+    ///
+    /// Every `rfe.toml` in the source, at its root or in a subdirectory,
+    /// makes a template; one whose manifest cannot be read or parsed is
+    /// listed with its `error` and no files. A source without any is a
+    /// single template at its root rendering the default scaffold files, as
+    /// `init` does.
+    pub fn of(reader: &SourceContentReader) -> Result<Self> {
+        let files = reader.list_files()?;
+        let mut templates = Vec::new();
+        for path in &files {
+            let subdir = match path.strip_suffix(MANIFEST_FILE) {
+                Some("") => None,
+                Some(dir) if dir.ends_with('/') => Some(dir.trim_end_matches('/')),
+                _ => continue,
+            };
+            let source = match subdir {
+                Some(subdir) => format!("{} (subdirectory {})", reader.describe(), subdir),
+                None => reader.describe(),
+            };
+            let manifest = reader
+                .read_file_contents(path)
+                .and_then(|contents| Manifest::parse(&contents, &source));
+            templates.push(match manifest {
+                Ok(manifest) => TemplateEntry::new(subdir, Some(&manifest), &files),
+                Err(e) => TemplateEntry::unavailable(subdir, e.to_string()),
+            });
+        }
+        if templates.is_empty() {
+            templates.push(TemplateEntry::new(None, None, &files));
+        }
+        Ok(Catalog { templates, files })
+    }
+}
+
+impl TemplateEntry {
+    /// The template in `subdir` described by `manifest`, among the source's
+    /// `files`
+    fn new(subdir: Option<&str>, manifest: Option<&Manifest>, files: &[String]) -> Self {
+        let prefix = subdir.map_or(String::new(), |subdir| format!("{}/", subdir));
+        let in_source = |source: &str| files.contains(&format!("{}{}", prefix, source));
+        let listed: Vec<TemplateFile> = match manifest {
+            Some(manifest) if !manifest.files.is_empty() => manifest
+                .files
+                .iter()
+                .map(|file| TemplateFile {
+                    source: file.source.clone(),
+                    destination: file.destination().to_string(),
+                    description: file.description.clone(),
+                    embedded: !in_source(&file.source),
+                })
+                .collect(),
+            _ => SCAFFOLD_FILES
+                .iter()
+                .map(|file| TemplateFile {
+                    source: file.to_string(),
+                    destination: file.to_string(),
+                    description: None,
+                    embedded: !in_source(file),
+                })
+                .collect(),
+        };
+        let info = manifest.map(|manifest| &manifest.template);
+        TemplateEntry {
+            subdir: subdir.map(String::from),
+            name: info.and_then(|info| info.name.clone()),
+            version: info.and_then(|info| info.version.clone()),
+            description: info.and_then(|info| info.description.clone()),
+            files: listed,
+            error: None,
+        }
+    }
+
+    /// The template in `subdir` whose manifest failed with `error`
+    fn unavailable(subdir: Option<&str>, error: String) -> Self {
+        TemplateEntry {
+            subdir: subdir.map(String::from),
+            name: None,
+            version: None,
+            description: None,
+            files: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
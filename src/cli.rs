@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use repo_file_expander::{ConflictPolicy, RfeError, SourceContentReader, SourceOptions, Variables};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -16,17 +17,204 @@ pub struct Cli {
 
 impl Cli {
     pub fn parse_and_resolve_options() -> Self {
-        let cli = Self::parse();
-        cli
+        Self::parse()
     }
 }
 
 #[derive(Subcommand, Clone)]
 pub enum Commands {
     #[command(about = "Scaffold devnev.yaml, devenv.nix, .gitignore and .envrc")]
-    Init {
-        target: Option<PathBuf>,
-        #[arg(short, long)]
-        source: Option<String>,
+    Init(InitArgs),
+    #[command(about = "Bring a scaffolded project up to date with its template")]
+    Update(UpdateArgs),
+    #[command(
+        about = "Report files that drifted from what rfe wrote or from the template",
+        visible_alias = "check"
+    )]
+    Status(StatusArgs),
+    #[command(
+        about = "Print a scaffold file as the template renders it",
+        visible_alias = "cat"
+    )]
+    Show(ShowArgs),
+    #[command(about = "List the templates and files a source offers")]
+    List(ListArgs),
+    #[command(about = "Remove the files rfe scaffolded, restoring what they replaced")]
+    Uninstall(UninstallArgs),
+    #[command(about = "Inspect and clean the cache of template repositories")]
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
     },
 }
+
+#[derive(Args, Clone)]
+pub struct InitArgs {
+    pub target: Option<PathBuf>,
+    #[command(flatten)]
+    pub source: SourceArgs,
+    /// Set a template variable; may be repeated
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_variable)]
+    pub vars: Vec<(String, String)>,
+    /// TOML file with answers for template variables
+    #[arg(long, value_name = "FILE")]
+    pub answers: Option<PathBuf>,
+    /// Never prompt for variables, even on a terminal
+    #[arg(long)]
+    pub no_input: bool,
+    /// Show the planned file operations and diffs without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// What to do with files that already exist with other contents:
+    /// skip, overwrite, backup (keep a `.orig` copy), merge or prompt;
+    /// prompting skips them without a terminal
+    #[arg(long, value_name = "POLICY", default_value = "prompt", value_parser = parse_conflict_policy)]
+    pub on_conflict: ConflictPolicy,
+}
+
+#[derive(Args, Clone)]
+pub struct UpdateArgs {
+    /// Project directory containing `.rfe.lock`
+    pub target: Option<PathBuf>,
+    /// Branch, tag or commit to update to; defaults to the one recorded
+    #[arg(long = "ref")]
+    pub git_ref: Option<String>,
+    /// Use the cached copy of a git source without touching the network
+    #[arg(long)]
+    pub offline: bool,
+    /// Only fetch git sources from this host; may be repeated
+    #[arg(long = "allow-host", value_name = "HOST")]
+    pub allowed_hosts: Vec<String>,
+    /// Set a template variable; may be repeated
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_variable)]
+    pub vars: Vec<(String, String)>,
+    /// Never prompt for variables, even on a terminal
+    #[arg(long)]
+    pub no_input: bool,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Write conflicting template changes to `.rej` files instead of
+    /// conflict markers
+    #[arg(long)]
+    pub reject: bool,
+}
+
+#[derive(Args, Clone)]
+pub struct StatusArgs {
+    /// Project directory containing `.rfe.lock`
+    pub target: Option<PathBuf>,
+    /// Branch, tag or commit to compare with; defaults to the one recorded
+    #[arg(long = "ref")]
+    pub git_ref: Option<String>,
+    /// Use the cached copy of a git source without touching the network
+    #[arg(long)]
+    pub offline: bool,
+    /// Only fetch git sources from this host; may be repeated
+    #[arg(long = "allow-host", value_name = "HOST")]
+    pub allowed_hosts: Vec<String>,
+    /// Exit with status 1 when any file is not unmodified
+    #[arg(long)]
+    pub exit_code: bool,
+    /// Print the status as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Clone)]
+pub struct ShowArgs {
+    /// File to print, by destination or by its path in the template
+    pub file: String,
+    #[command(flatten)]
+    pub source: SourceArgs,
+    /// Set a template variable; may be repeated
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_variable)]
+    pub vars: Vec<(String, String)>,
+    /// Never prompt for variables, even on a terminal
+    #[arg(long)]
+    pub no_input: bool,
+    /// Report on stderr where the file came from
+    #[arg(long)]
+    pub explain: bool,
+}
+
+#[derive(Args, Clone)]
+pub struct ListArgs {
+    #[command(flatten)]
+    pub source: SourceArgs,
+    /// Print the templates and files as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Clone)]
+pub struct UninstallArgs {
+    /// Project directory containing `.rfe.lock`
+    pub target: Option<PathBuf>,
+    /// Remove or restore files even when they changed since rfe wrote them
+    #[arg(long)]
+    pub force: bool,
+    /// Show what would be removed or restored without touching anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Options selecting the template source, shared by every command reading one
+#[derive(Args, Clone)]
+pub struct SourceArgs {
+    #[arg(short, long)]
+    pub source: Option<String>,
+    /// Branch, tag or commit to read a git source at
+    #[arg(long = "ref", requires = "source")]
+    pub git_ref: Option<String>,
+    /// Use the cached copy of a git source without touching the network
+    #[arg(long)]
+    pub offline: bool,
+    /// Only fetch git sources from this host; may be repeated
+    #[arg(long = "allow-host", value_name = "HOST")]
+    pub allowed_hosts: Vec<String>,
+    /// Subdirectory of the source holding the template; `source//subdir` also
+    /// works for git URLs
+    #[arg(long, requires = "source")]
+    pub subdir: Option<String>,
+}
+
+impl SourceArgs {
+    /// Open the selected source, or the embedded scaffold when none is given
+    pub fn open(&self) -> Result<SourceContentReader, RfeError> {
+        let options = SourceOptions {
+            git_ref: self.git_ref.clone(),
+            offline: self.offline,
+            allowed_hosts: self.allowed_hosts.clone(),
+            subdir: self.subdir.clone(),
+            ..SourceOptions::default()
+        };
+        match &self.source {
+            Some(source) => SourceContentReader::open(source, &options),
+            None => Ok(SourceContentReader::embedded()),
+        }
+    }
+}
+
+#[derive(Subcommand, Clone)]
+pub enum CacheCommand {
+    #[command(about = "List cached repositories")]
+    List,
+    #[command(about = "Remove repositories that have not been used recently")]
+    Prune {
+        /// Remove entries unused for more than this many days
+        #[arg(long, default_value_t = 30)]
+        max_age_days: u64,
+    },
+    #[command(about = "Remove every cached repository")]
+    Clear,
+}
+
+fn parse_variable(assignment: &str) -> Result<(String, String), String> {
+    Variables::parse_assignment(assignment).map_err(|e| e.to_string())
+}
+
+fn parse_conflict_policy(name: &str) -> Result<ConflictPolicy, String> {
+    ConflictPolicy::parse(name)
+        .ok_or_else(|| format!("expected one of {}", ConflictPolicy::NAMES.join(", ")))
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors surfaced by rfe, each mapped to its own process exit code
+#[derive(Debug)]
+pub enum RfeError {
+    /// The source path does not exist on disk
+    SourceNotFound { path: PathBuf },
+    /// The source is neither a directory nor a supported git URL
+    InvalidSource { source: String },
+    /// The git URL's host is not in the configured allowlist
+    HostNotAllowed { host: String },
+    /// Cloning a git source failed
+    CloneFailed { url: String, error: git2::Error },
+    /// Reading objects from a git repository failed
+    Git { url: String, error: git2::Error },
+    /// `--offline` was given but the repository is not in the cache
+    NotCached { url: String },
+    /// The requested branch, tag or commit does not exist in the source
+    RefNotFound { reference: String, source: String },
+    /// A branch, tag or commit was requested for a source that is not a git
+    /// repository
+    RefUnsupported { reference: String, source: String },
+    /// The template subdirectory has no files in the source
+    SubdirNotFound { subdir: String, source: String },
+    /// The template's `rfe.toml` could not be parsed
+    InvalidManifest { source: String, message: String },
+    /// The template requires a newer rfe
+    IncompatibleTemplate { required: String, current: String },
+    /// A `--var` assignment is not of the form `name=value`
+    InvalidVariable { assignment: String },
+    /// Variables the manifest marks as required have no value
+    MissingVariables { names: Vec<String> },
+    /// A variable's value fails its manifest validation
+    InvalidAnswer { name: String, message: String },
+    /// An answers file is not a TOML table of simple values
+    InvalidAnswersFile { path: PathBuf, message: String },
+    /// The `.rfe.lock` of a project cannot be read
+    InvalidLockfile { path: PathBuf, message: String },
+    /// The directory has no `.rfe.lock`, so it was not scaffolded by rfe
+    NotScaffolded { path: PathBuf },
+    /// Uninstalling would throw away changes made to files since rfe wrote them
+    LocallyModified { paths: Vec<String> },
+    /// Uninstalling would remove files whose earlier contents rfe cannot
+    /// restore
+    Unrecoverable { paths: Vec<String> },
+    /// `update` left files with conflict markers or `.rej` files
+    UnresolvedConflicts { paths: Vec<String> },
+    /// A path is absolute or climbs out of the directory it belongs to
+    UnsafePath { path: String },
+    /// A template file failed to render
+    Template { file: String, message: String },
+    /// The file is neither in the source nor in the embedded scaffold;
+    /// `source` is empty when only the embedded scaffold was consulted
+    FileMissingEverywhere { filename: String, source: String },
+    /// The file exists in `source` but is not valid UTF-8
+    NotUtf8 { path: PathBuf, source: String },
+    /// Reading or writing a file failed
+    Io { path: PathBuf, error: io::Error },
+}
+
+impl RfeError {
+    /// Exit code reported to the shell for this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            RfeError::SourceNotFound { .. } => 3,
+            RfeError::InvalidSource { .. } => 4,
+            RfeError::CloneFailed { .. } => 5,
+            RfeError::FileMissingEverywhere { .. } => 6,
+            RfeError::NotUtf8 { .. } => 7,
+            RfeError::Io { .. } => 8,
+            RfeError::RefNotFound { .. } => 9,
+            RfeError::Git { .. } => 10,
+            RfeError::NotCached { .. } => 11,
+            RfeError::HostNotAllowed { .. } => 12,
+            RfeError::SubdirNotFound { .. } => 13,
+            RfeError::InvalidManifest { .. } => 14,
+            RfeError::IncompatibleTemplate { .. } => 15,
+            RfeError::InvalidVariable { .. } => 16,
+            RfeError::MissingVariables { .. } => 17,
+            RfeError::Template { .. } => 18,
+            RfeError::InvalidAnswer { .. } => 19,
+            RfeError::InvalidAnswersFile { .. } => 20,
+            RfeError::InvalidLockfile { .. } => 21,
+            RfeError::NotScaffolded { .. } => 22,
+            RfeError::LocallyModified { .. } => 23,
+            RfeError::Unrecoverable { .. } => 24,
+            RfeError::UnsafePath { .. } => 25,
+            RfeError::UnresolvedConflicts { .. } => 26,
+            RfeError::RefUnsupported { .. } => 27,
+        }
+    }
+
+    pub(crate) fn io(path: impl Into<PathBuf>, error: io::Error) -> Self {
+        RfeError::Io {
+            path: path.into(),
+            error,
+        }
+    }
+}
+
+impl fmt::Display for RfeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RfeError::SourceNotFound { path } => {
+                write!(f, "source {} does not exist", path.display())
+            }
+            RfeError::InvalidSource { source } => write!(
+                f,
+                "source {} is neither a directory nor a supported git URL",
+                source
+            ),
+            RfeError::HostNotAllowed { host } => {
+                write!(f, "host {} is not in the list of allowed hosts", host)
+            }
+            RfeError::CloneFailed { url, error } => {
+                write!(f, "failed to clone {}: {}", url, error.message())
+            }
+            RfeError::Git { url, error } => write!(f, "{}: {}", url, error.message()),
+            RfeError::NotCached { url } => {
+                write!(f, "{} is not cached; run without --offline first", url)
+            }
+            RfeError::RefNotFound { reference, source } => {
+                write!(f, "ref {} not found in {}", reference, source)
+            }
+            RfeError::RefUnsupported { reference, source } => write!(
+                f,
+                "--ref {} needs a git source, but {} is not a git repository",
+                reference, source
+            ),
+            RfeError::SubdirNotFound { subdir, source } => {
+                write!(f, "subdirectory {} not found in {}", subdir, source)
+            }
+            RfeError::InvalidManifest { source, message } => {
+                write!(f, "invalid rfe.toml in {}: {}", source, message)
+            }
+            RfeError::IncompatibleTemplate { required, current } => write!(
+                f,
+                "template requires rfe {} or newer, this is rfe {}",
+                required, current
+            ),
+            RfeError::InvalidVariable { assignment } => {
+                write!(f, "variable {} is not of the form name=value", assignment)
+            }
+            RfeError::MissingVariables { names } => {
+                write!(f, "missing required variables: {}", names.join(", "))
+            }
+            RfeError::InvalidAnswer { name, message } => {
+                write!(f, "invalid value for {}: {}", name, message)
+            }
+            RfeError::InvalidAnswersFile { path, message } => {
+                write!(f, "invalid answers file {}: {}", path.display(), message)
+            }
+            RfeError::InvalidLockfile { path, message } => {
+                write!(f, "invalid lockfile {}: {}", path.display(), message)
+            }
+            RfeError::NotScaffolded { path } => write!(
+                f,
+                "{} has no .rfe.lock; scaffold it with rfe init first",
+                path.display()
+            ),
+            RfeError::LocallyModified { paths } => write!(
+                f,
+                "{} changed since rfe wrote them; use --force to discard the changes",
+                paths.join(", ")
+            ),
+            RfeError::Unrecoverable { paths } => write!(
+                f,
+                "{} may not be rfe's to remove and cannot be restored; use --force to remove them anyway",
+                paths.join(", ")
+            ),
+            RfeError::UnresolvedConflicts { paths } => write!(
+                f,
+                "{} have conflicting changes; resolve the conflict markers or .rej files and run rfe update again",
+                paths.join(", ")
+            ),
+            RfeError::UnsafePath { path } => {
+                write!(f, "{} must be a relative path without `..`", path)
+            }
+            RfeError::Template { file, message } => {
+                write!(f, "failed to render {}: {}", file, message)
+            }
+            RfeError::FileMissingEverywhere { filename, source } if source.is_empty() => {
+                write!(f, "{} is not part of the embedded scaffold", filename)
+            }
+            RfeError::FileMissingEverywhere { filename, source } => write!(
+                f,
+                "{} was found neither in {} nor in the embedded scaffold",
+                filename, source
+            ),
+            RfeError::NotUtf8 { path, source } => {
+                write!(f, "{} in {} is not valid UTF-8", path.display(), source)
+            }
+            RfeError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for RfeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RfeError::CloneFailed { error, .. } => Some(error),
+            RfeError::Git { error, .. } => Some(error),
+            RfeError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, RfeError>;
@@ -0,0 +1,38 @@
+//! Scaffold devenv projects from a local directory, a git repository or the
+//! defaults embedded in the binary.
+//!
+//! ```no_run
+//! use repo_file_expander::{render_scaffold, SourceContentReader, Variables, WritePlan};
+//!
+//! let reader = SourceContentReader::new("https://github.com/org/templates")?;
+//! let variables = Variables::resolve(reader.manifest()?.as_ref(), Variables::from_env(), &[])?;
+//! let plan = WritePlan::new("my-project", render_scaffold(&reader, &variables)?)?;
+//! plan.apply()?;
+//! # Ok::<(), repo_file_expander::RfeError>(())
+//! ```
+
+pub mod cache;
+pub mod catalog;
+pub mod error;
+pub mod lock;
+pub mod manifest;
+pub mod merge;
+pub mod plan;
+pub mod prompt;
+pub mod source;
+pub mod status;
+pub mod template;
+pub mod uninstall;
+pub mod update;
+
+mod scaffold;
+mod stuff;
+
+pub use error::{Result, RfeError};
+pub use lock::Lockfile;
+pub use manifest::Manifest;
+pub use plan::{ConflictPolicy, FileAction, PlannedFile, WritePlan};
+pub use scaffold::{render_file, render_scaffold, write_scaffold, RenderedFile, SCAFFOLD_FILES};
+pub use source::TemplateSource;
+pub use stuff::{split_subdir, Provenance, SourceContentReader, SourceOptions};
+pub use template::Variables;
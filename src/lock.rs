@@ -0,0 +1,280 @@
+use crate::error::{Result, RfeError};
+use crate::manifest::Manifest;
+use crate::plan::{check_relative, FileAction, WritePlan};
+use crate::stuff::{SourceContentReader, SourceOptions};
+use crate::template::Variables;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// File in the target directory recording where its scaffold came from
+pub const LOCK_FILE: &str = ".rfe.lock";
+
+/// Format version written to `.rfe.lock`
+///
+/// Version 1 recorded files that already matched the template as `create`,
+/// so its `create` entries may be files the user had before rfe. A lockfile
+/// carrying entries over from a version 1 lockfile keeps version 1.
+pub const LOCK_VERSION: u32 = 2;
+
+/// Where a project's scaffold came from and what rfe wrote, as stored in
+/// `.rfe.lock`
+///
+/// ```toml
+/// version = 2
+///
+/// [source]
+/// url = "https://github.com/org/templates"
+/// subdir = "rust"
+/// ref = "main"
+/// commit = "4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+///
+/// [template]
+/// name = "rust"
+/// version = "1.2.0"
+///
+/// [variables]
+/// project_name = "demo"
+///
+/// [[files]]
+/// path = "devenv.nix"
+/// action = "create"
+/// sha256 = "..."
+/// template_sha256 = "..."
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Lockfile {
+    pub version: u32,
+    pub source: LockedSource,
+    #[serde(default)]
+    pub template: LockedTemplate,
+    /// Values of the template's declared variables the files were rendered
+    /// with; `init` and `update` replay them
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default)]
+    pub files: Vec<LockedFile>,
+}
+
+/// The template source a scaffold was rendered from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedSource {
+    /// Path or URL of the source, `None` for the embedded scaffold
+    pub url: Option<String>,
+    pub subdir: Option<String>,
+    /// Branch, tag or commit the source was asked to be read at
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+    /// Commit the source was resolved to, for git sources
+    pub commit: Option<String>,
+}
+
+/// Name and version of the template from its manifest
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedTemplate {
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+/// A file rfe manages in the project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedFile {
+    /// Path relative to the project directory
+    pub path: String,
+    /// How the file was written
+    pub action: FileAction,
+    /// SHA-256 of the file as rfe left it
+    pub sha256: String,
+    /// SHA-256 of the rendered template file
+    pub template_sha256: String,
+}
+
+impl LockedSource {
+    /// Describe the source `reader` reads from
+    ///
+    /// Local paths are made absolute, so the lockfile finds the source
+    /// whatever directory rfe is later run from.
+    pub fn of(reader: &SourceContentReader) -> Self {
+        let url = reader.location().map(|location| {
+            match Path::new(location)
+                .exists()
+                .then(|| fs::canonicalize(location))
+            {
+                Some(Ok(path)) => path.to_string_lossy().into_owned(),
+                _ => location.to_string(),
+            }
+        });
+        LockedSource {
+            url,
+            subdir: reader.subdir().map(String::from),
+            git_ref: reader.git_ref().map(String::from),
+            commit: reader.revision(),
+        }
+    }
+
+    /// Open the recorded source at `git_ref`, or the embedded scaffold
+    ///
+    /// The recorded subdirectory is used; other settings come from `options`.
+    pub fn open(
+        &self,
+        git_ref: Option<&str>,
+        options: &SourceOptions,
+    ) -> Result<SourceContentReader> {
+        let url = match &self.url {
+            Some(url) => url,
+            None => return Ok(SourceContentReader::embedded()),
+        };
+        let options = SourceOptions {
+            git_ref: git_ref.map(String::from),
+            subdir: self.subdir.clone(),
+            ..options.clone()
+        };
+        SourceContentReader::open(url, &options)
+    }
+}
+
+/// Hex-encoded SHA-256 of `contents`, as recorded in `.rfe.lock`
+pub fn content_hash(contents: &str) -> String {
+    format!("{:x}", Sha256::digest(contents.as_bytes()))
+}
+
+impl Lockfile {
+    /// Record an applied `plan` rendered from `source` with `variables`
+    ///
+    /// Conflicting files the plan skipped belong to the user and are only
+    /// recorded when `previous` already managed them. Files that were
+    /// already up to date keep the action they were first written with, or
+    /// are recorded as `skip` when the user had them before rfe.
+    pub fn record(
+        source: LockedSource,
+        manifest: Option<&Manifest>,
+        variables: &Variables,
+        plan: &WritePlan,
+        previous: Option<&Lockfile>,
+    ) -> Self {
+        let previous_file = |path: &str| previous.and_then(|lock| lock.file(path));
+
+        let mut files = Vec::new();
+        let mut carried = false;
+        for file in plan.files() {
+            let action = match file.action {
+                FileAction::Skip if file.is_conflict() => {
+                    let kept = previous_file(&file.path).cloned();
+                    carried |= kept.is_some();
+                    files.extend(kept);
+                    continue;
+                }
+                FileAction::Skip => match previous_file(&file.path) {
+                    Some(locked) => {
+                        carried = true;
+                        locked.action
+                    }
+                    // The user already had the file; it is not rfe's to remove
+                    None => FileAction::Skip,
+                },
+                action => action,
+            };
+            files.push(LockedFile {
+                path: file.path.clone(),
+                action,
+                sha256: content_hash(&file.result()),
+                template_sha256: content_hash(&file.contents),
+            });
+        }
+
+        let mut lock = Self::new(source, manifest, variables, files);
+        if let (true, Some(previous)) = (carried, previous) {
+            lock.version = lock.version.min(previous.version);
+        }
+        lock
+    }
+
+    /// A lockfile for `files` rendered from `source` with `variables`
+    ///
+    /// Only the variables `manifest` declares are recorded; without a
+    /// manifest there is nothing to tell them apart, so all are.
+    pub fn new(
+        source: LockedSource,
+        manifest: Option<&Manifest>,
+        variables: &Variables,
+        files: Vec<LockedFile>,
+    ) -> Self {
+        let template = manifest.map(|manifest| &manifest.template);
+        Lockfile {
+            version: LOCK_VERSION,
+            source,
+            template: LockedTemplate {
+                name: template.and_then(|t| t.name.clone()),
+                version: template.and_then(|t| t.version.clone()),
+            },
+            variables: manifest
+                .map_or_else(|| variables.clone(), |m| variables.declared_in(m))
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            files,
+        }
+    }
+
+    /// The entry for the file at `path`, if rfe manages it
+    pub fn file(&self, path: &str) -> Option<&LockedFile> {
+        self.files.iter().find(|file| file.path == path)
+    }
+
+    /// The recorded variable values
+    pub fn variables(&self) -> Variables {
+        let mut variables = Variables::new();
+        for (name, value) in &self.variables {
+            variables.set(name, value);
+        }
+        variables
+    }
+
+    /// Load the lockfile of the project in `target`
+    pub fn find(target: &Path) -> Result<Self> {
+        let path = target.join(LOCK_FILE);
+        if !path.is_file() {
+            return Err(RfeError::NotScaffolded {
+                path: target.to_path_buf(),
+            });
+        }
+        Self::load(&path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| RfeError::io(path, e))?;
+        let invalid = |message: String| RfeError::InvalidLockfile {
+            path: path.to_path_buf(),
+            message,
+        };
+        let lock: Lockfile = toml::from_str(&contents).map_err(|e| invalid(e.message().into()))?;
+        if lock.version > LOCK_VERSION {
+            return Err(invalid(format!(
+                "version {} was written by a newer rfe",
+                lock.version
+            )));
+        }
+        if let Some(file) = lock.files.iter().find(|f| check_relative(&f.path).is_err()) {
+            return Err(invalid(format!(
+                "{} is not a path inside the project",
+                file.path
+            )));
+        }
+        Ok(lock)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = toml::to_string(self).map_err(|e| RfeError::InvalidLockfile {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        let contents = format!("# Written by rfe; do not edit by hand\n{}", contents);
+        fs::write(path, contents).map_err(|e| RfeError::io(path, e))
+    }
+}
@@ -1,24 +1,45 @@
 use clap::crate_version;
-use cli::Commands;
+use cli::{
+    CacheCommand, Commands, InitArgs, ListArgs, ShowArgs, StatusArgs, UninstallArgs, UpdateArgs,
+};
+use repo_file_expander::cache::Cache;
+use repo_file_expander::catalog::{Catalog, TemplateEntry};
+use repo_file_expander::lock::{LockedSource, LOCK_FILE};
+use repo_file_expander::prompt::Prompter;
+use repo_file_expander::status::{FileState, ProjectStatus};
+use repo_file_expander::uninstall::UninstallPlan;
+use repo_file_expander::update::{UpdateOutcome, UpdatePlan};
+use repo_file_expander::{
+    render_file, render_scaffold, FileAction, Lockfile, Manifest, RfeError, SourceOptions,
+    Variables, WritePlan,
+};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
 
 mod cli;
-mod stuff;
-
-/// Demonstrate the usage of SourceContentReader
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Test cases for different source types
-    // let test_sources = vec![
-    //     "/home/mgr8/stuff/rust/my-redis/",        // Local git repository
-    //     "https://github.com/mrityunjaygr8/guzei", // Remote git repository
-    //     "../../golang/guzei",                     // Local git repository
-    //     "invalid/path",                           // Unknown source
-    // ];
 
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Parse the command line and dispatch to the subcommand's handler
+///
+/// Without a subcommand the version is printed. Returns the exit code for
+/// commands that report one of their own, such as `status --exit-code`.
+fn run() -> Result<ExitCode, RfeError> {
     let cli = cli::Cli::parse_and_resolve_options();
 
     let print_version = || {
         println!("rfe {} ", crate_version!());
-        Ok(())
+        Ok(ExitCode::SUCCESS)
     };
 
     let command = match cli.command {
@@ -27,24 +48,417 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     match command {
-        Commands::Init { target, source } => {
-            println!("target: {:?}, source: {:?}", target, source);
-            let target_file = "devenv.nix";
+        Commands::Init(args) => run_init(args)?,
+        Commands::Update(args) => run_update(args)?,
+        Commands::Status(args) => return run_status(args),
+        Commands::Show(args) => run_show(args)?,
+        Commands::List(args) => run_list(args)?,
+        Commands::Uninstall(args) => run_uninstall(args)?,
+        Commands::Cache { action } => run_cache(action)?,
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_init(args: InitArgs) -> Result<(), RfeError> {
+    let target = args.target.clone().unwrap_or_else(|| PathBuf::from("."));
+    let reader = args.source.open()?;
+    println!("source: {}", reader.describe());
+    if let Some(commit) = reader.revision() {
+        println!("commit: {}", commit);
+    }
+    let manifest = reader.manifest()?;
+    if let Some(manifest) = &manifest {
+        let info = &manifest.template;
+        let name = info.name.as_deref().unwrap_or("(unnamed)");
+        match &info.version {
+            Some(version) => println!("template: {} {}", name, version),
+            None => println!("template: {}", name),
+        }
+    }
+
+    let lock_path = target.join(LOCK_FILE);
+    let previous = match lock_path.is_file() {
+        true => Some(Lockfile::load(&lock_path)?),
+        false => None,
+    };
+    let variables = resolve_variables(previous.as_ref(), manifest.as_ref(), &args)?;
+    let mut plan = WritePlan::new(&target, render_scaffold(&reader, &variables)?)?;
+    resolve_conflicts(&mut plan, &args)?;
+    for file in plan.files() {
+        for conflict in file.merged().into_iter().flat_map(|m| m.conflicts) {
+            eprintln!("warning: {}: {}", file.path, conflict);
+        }
+    }
+    if args.dry_run {
+        for file in plan.files() {
+            println!("{} {}", file.action, file.path);
+            print!("{}", file.diff());
+        }
+        return Ok(());
+    }
+    for path in plan.apply()? {
+        println!("wrote {}", path.display());
+    }
+    let lock = Lockfile::record(
+        LockedSource::of(&reader),
+        manifest.as_ref(),
+        &variables,
+        &plan,
+        previous.as_ref(),
+    );
+    lock.save(&lock_path)?;
+    println!("wrote {}", lock_path.display());
+    Ok(())
+}
+
+/// Gather template variables for `init`
+///
+/// Answers recorded in the `previous` lockfile are replayed, then the
+/// `--answers` file, the environment and `--var` take precedence in turn.
+/// Declared variables still without a value are prompted for on a terminal.
+fn resolve_variables(
+    previous: Option<&Lockfile>,
+    manifest: Option<&Manifest>,
+    args: &InitArgs,
+) -> Result<Variables, RfeError> {
+    let mut provided = previous.map(Lockfile::variables).unwrap_or_default();
+    if let Some(answers) = &args.answers {
+        provided.extend(Variables::load(answers)?);
+    }
+    provided.extend(Variables::from_env());
+    for (name, value) in &args.vars {
+        provided.set(name, value);
+    }
+
+    complete_variables(manifest, provided, args.no_input)
+}
+
+/// Prompt on a terminal for declared variables `provided` has no value for,
+/// then validate everything against the manifest
+fn complete_variables(
+    manifest: Option<&Manifest>,
+    mut provided: Variables,
+    no_input: bool,
+) -> Result<Variables, RfeError> {
+    if let Some(manifest) = manifest {
+        if !no_input && io::stdin().is_terminal() {
+            let mut prompter = Prompter::new(io::stdin().lock(), io::stderr());
+            let prompted = prompter.ask_missing(manifest, &provided)?;
+            provided.extend(prompted);
+        }
+    }
+    Variables::resolve(manifest, provided, &[])
+}
+
+/// Apply `--on-conflict` to files that already exist with other contents
+///
+/// `prompt` asks per file on a terminal; without one, or with `--no-input`
+/// or `--dry-run`, conflicting files are skipped.
+fn resolve_conflicts(plan: &mut WritePlan, args: &InitArgs) -> Result<(), RfeError> {
+    if let Some(action) = args.on_conflict.action() {
+        return plan.resolve_conflicts(|_| Ok(action));
+    }
+    if args.no_input || args.dry_run || !io::stdin().is_terminal() {
+        return plan.resolve_conflicts(|_| Ok(FileAction::Skip));
+    }
+    let mut prompter = Prompter::new(io::stdin().lock(), io::stderr());
+    plan.resolve_conflicts(|file| prompter.choose_action(file))
+}
+
+fn run_update(args: UpdateArgs) -> Result<(), RfeError> {
+    let target = args.target.clone().unwrap_or_else(|| PathBuf::from("."));
+    let lock = Lockfile::find(&target)?;
+    let options = SourceOptions {
+        offline: args.offline,
+        allowed_hosts: args.allowed_hosts.clone(),
+        ..SourceOptions::default()
+    };
+
+    let git_ref = args.git_ref.as_deref().or(lock.source.git_ref.as_deref());
+    let reader = lock.source.open(git_ref, &options)?;
+    println!("source: {}", reader.describe());
+    match (&lock.source.commit, reader.revision()) {
+        (Some(from), Some(to)) if *from != to => println!("commit: {} -> {}", from, to),
+        (_, Some(to)) => println!("commit: {}", to),
+        _ => {}
+    }
+    let manifest = reader.manifest()?;
+
+    let mut provided = lock.variables();
+    provided.extend(Variables::from_env());
+    for (name, value) in &args.vars {
+        provided.set(name, value);
+    }
+    let variables = complete_variables(manifest.as_ref(), provided, args.no_input)?;
+    let next = render_scaffold(&reader, &variables)?;
+
+    // The recorded commit rendered with the recorded answers is the common
+    // ancestor; prefer the cached copy since the fetch above has just run
+    let base = lock.source.commit.as_deref().and_then(|commit| {
+        let offline = SourceOptions {
+            offline: true,
+            ..options.clone()
+        };
+        let base = lock
+            .source
+            .open(Some(commit), &offline)
+            .or_else(|_| lock.source.open(Some(commit), &options))
+            .and_then(|base| render_scaffold(&base, &lock.variables()));
+        if let Err(e) = &base {
+            eprintln!("warning: cannot read the recorded template: {}", e);
+        }
+        base.ok()
+    });
+
+    let plan = UpdatePlan::new(&target, &lock, base.as_deref(), next, args.reject)?;
+    for file in plan.files() {
+        if file.outcome != UpdateOutcome::Unchanged {
+            println!("{} {}", file.outcome, file.path);
+        }
+        for note in &file.notes {
+            eprintln!("warning: {}: {}", file.path, note);
+        }
+        if args.dry_run {
+            print!("{}", file.diff());
+        }
+    }
+    if !args.dry_run {
+        for path in plan.apply()? {
+            println!("wrote {}", path.display());
+        }
+        let lock_path = target.join(LOCK_FILE);
+        plan.lockfile(LockedSource::of(&reader), manifest.as_ref(), &variables)
+            .save(&lock_path)?;
+        println!("wrote {}", lock_path.display());
+    }
+
+    let unresolved = plan.unresolved();
+    if !unresolved.is_empty() {
+        return Err(RfeError::UnresolvedConflicts { paths: unresolved });
+    }
+    Ok(())
+}
 
-            let reader = stuff::SourceContentReader::new(source.unwrap().as_str()).unwrap();
+/// Compare the project with its lockfile and the template at the recorded
+/// (or given) ref
+///
+/// The template is rendered with the recorded answers only, so the check
+/// never prompts. With `--exit-code`, any drift exits with status 1.
+fn run_status(args: StatusArgs) -> Result<ExitCode, RfeError> {
+    let target = args.target.clone().unwrap_or_else(|| PathBuf::from("."));
+    let lock = Lockfile::find(&target)?;
+    let options = SourceOptions {
+        offline: args.offline,
+        allowed_hosts: args.allowed_hosts.clone(),
+        ..SourceOptions::default()
+    };
+    let git_ref = args.git_ref.as_deref().or(lock.source.git_ref.as_deref());
+    let reader = lock.source.open(git_ref, &options)?;
+    let manifest = reader.manifest()?;
+    let variables = Variables::resolve(manifest.as_ref(), lock.variables(), &[])?;
+    let status = ProjectStatus::new(&target, &lock, &render_scaffold(&reader, &variables)?)?;
 
-            // Try to read file contents
-            match reader.read_file_contents(target_file) {
-                Ok(contents) => {
-                    println!("File contents (first 200 chars):");
-                    println!("{}", &contents[..contents.len().min(200)]);
+    if args.json {
+        let report = serde_json::json!({
+            "source": lock.source,
+            "commit": reader.revision(),
+            "clean": status.is_clean(),
+            "files": status.files(),
+        });
+        println!("{:#}", report);
+    } else {
+        println!("source: {}", reader.describe());
+        match (&lock.source.commit, reader.revision()) {
+            (Some(from), Some(to)) if *from != to => println!("commit: {} -> {}", from, to),
+            (_, Some(to)) => println!("commit: {}", to),
+            _ => {}
+        }
+        for file in status.files() {
+            match file.state {
+                FileState::Unmodified => {}
+                FileState::Modified | FileState::Missing if file.template_changed => {
+                    println!("{} {} (template changed)", file.state, file.path)
                 }
-                Err(e) => println!("Error reading file: {}", e),
+                state => println!("{} {}", state, file.path),
             }
         }
+        if status.is_clean() {
+            println!("{} managed file(s) unmodified", status.files().len());
+        }
     }
 
-    // // File to search for in each source
+    match args.exit_code && !status.is_clean() {
+        true => Ok(ExitCode::from(1)),
+        false => Ok(ExitCode::SUCCESS),
+    }
+}
+
+fn run_show(args: ShowArgs) -> Result<(), RfeError> {
+    let reader = args.source.open()?;
+    let manifest = reader.manifest()?;
+    let mut provided = Variables::from_env();
+    for (name, value) in &args.vars {
+        provided.set(name, value);
+    }
+    let variables = complete_variables(manifest.as_ref(), provided, args.no_input)?;
+    let (file, provenance) = render_file(&reader, &variables, &args.file)?;
+
+    if args.explain {
+        eprintln!("{}: {}", file.path, provenance);
+        if !variables.is_empty() {
+            let values: Vec<String> = variables
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            eprintln!("rendered with {}", values.join(", "));
+        }
+    }
+    print!("{}", file.contents);
+    Ok(())
+}
+
+fn run_list(args: ListArgs) -> Result<(), RfeError> {
+    let reader = args.source.open()?;
+    let catalog = Catalog::of(&reader)?;
+    if args.json {
+        let report = serde_json::json!({
+            "source": reader.describe(),
+            "commit": reader.revision(),
+            "templates": catalog.templates,
+            "files": catalog.files,
+        });
+        println!("{:#}", report);
+        return Ok(());
+    }
+
+    println!("source: {}", reader.describe());
+    if let Some(commit) = reader.revision() {
+        println!("commit: {}", commit);
+    }
+    let or_dash = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    let name = |template: &TemplateEntry| {
+        template
+            .name
+            .clone()
+            .or_else(|| template.subdir.clone())
+            .unwrap_or_else(|| "(unnamed)".to_string())
+    };
+
+    let mut templates = vec![["NAME", "VERSION", "SUBDIR", "DESCRIPTION"].map(String::from)];
+    let mut files = vec![["TEMPLATE", "FILE", "DESTINATION", "DESCRIPTION"].map(String::from)];
+    for template in &catalog.templates {
+        templates.push([
+            name(template),
+            or_dash(&template.version),
+            or_dash(&template.subdir),
+            match &template.error {
+                Some(error) => format!("unavailable: {}", error),
+                None => or_dash(&template.description),
+            },
+        ]);
+        for file in &template.files {
+            let description = match file.embedded {
+                true => "(embedded scaffold)".to_string(),
+                false => or_dash(&file.description),
+            };
+            files.push([
+                name(template),
+                file.source.clone(),
+                file.destination.clone(),
+                description,
+            ]);
+        }
+    }
+    println!();
+    print_table(&templates);
+    println!();
+    print_table(&files);
+    Ok(())
+}
 
+/// Print `rows` with every column but the last padded to a common width
+fn print_table<const N: usize>(rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let mut line = String::new();
+        for (i, cell) in row.iter().enumerate() {
+            match i + 1 == N {
+                true => line.push_str(cell),
+                false => line.push_str(&format!("{:width$}  ", cell, width = widths[i])),
+            }
+        }
+        println!("{}", line.trim_end());
+    }
+}
+
+fn run_uninstall(args: UninstallArgs) -> Result<(), RfeError> {
+    let target = args.target.clone().unwrap_or_else(|| PathBuf::from("."));
+    let lock = Lockfile::find(&target)?;
+    let plan = UninstallPlan::new(&target, &lock)?;
+    if !args.dry_run {
+        plan.apply(args.force)?;
+    }
+    for file in plan.files() {
+        match file.modified {
+            true => println!("{} {} (modified)", file.action, file.path),
+            false => println!("{} {}", file.action, file.path),
+        }
+        if let Some(note) = &file.note {
+            eprintln!("warning: {}: {}", file.path, note);
+        }
+    }
+    if !args.dry_run {
+        println!("removed {}", target.join(LOCK_FILE).display());
+    }
+    Ok(())
+}
+
+fn run_cache(action: CacheCommand) -> Result<(), RfeError> {
+    let cache = match Cache::from_env() {
+        Some(cache) => cache,
+        None => {
+            println!("no cache directory: set RFE_CACHE_DIR, XDG_CACHE_HOME or HOME");
+            return Ok(());
+        }
+    };
+
+    match action {
+        CacheCommand::List => {
+            for entry in cache.entries()? {
+                let age = entry
+                    .last_used
+                    .and_then(|used| SystemTime::now().duration_since(used).ok())
+                    .map(|age| format!("{}d", age.as_secs() / 86_400))
+                    .unwrap_or_else(|| "-".to_string());
+                println!(
+                    "{}\t{}\t{} KiB\tlast used {}",
+                    entry.key,
+                    entry.url.as_deref().unwrap_or("?"),
+                    entry.size / 1024,
+                    age
+                );
+            }
+        }
+        CacheCommand::Prune { max_age_days } => {
+            let max_age = Duration::from_secs(max_age_days * 86_400);
+            for entry in cache.prune(max_age)? {
+                println!("removed {}", entry.key);
+            }
+        }
+        CacheCommand::Clear => {
+            let removed = cache.clear()?;
+            println!(
+                "removed {} entries from {}",
+                removed,
+                cache.root().display()
+            );
+        }
+    }
     Ok(())
 }
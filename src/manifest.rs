@@ -0,0 +1,207 @@
+use crate::error::{Result, RfeError};
+use crate::plan::check_relative;
+use regex::Regex;
+use serde::Deserialize;
+
+/// Name of the manifest file at the root of a template
+pub const MANIFEST_FILE: &str = "rfe.toml";
+
+/// Template manifest (`rfe.toml`) describing what a template provides
+///
+/// ```toml
+/// [template]
+/// name = "rust"
+/// description = "Rust service with devenv"
+/// version = "1.2.0"
+/// min_rfe_version = "0.1.0"
+///
+/// [[files]]
+/// source = "nix/devenv.nix"
+/// destination = "devenv.nix"
+/// description = "devenv configuration"
+///
+/// [[variables]]
+/// name = "project_name"
+/// description = "Name of the project"
+/// required = true
+/// pattern = "[a-z][a-z0-9-]*"
+///
+/// [[variables]]
+/// name = "database"
+/// type = "choice"
+/// choices = ["postgres", "mysql"]
+/// default = "postgres"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub template: TemplateInfo,
+    #[serde(default)]
+    pub files: Vec<ManifestFile>,
+    #[serde(default)]
+    pub variables: Vec<Variable>,
+}
+
+/// Metadata about the template itself
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateInfo {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    /// Oldest rfe release able to render the template
+    pub min_rfe_version: Option<String>,
+}
+
+/// A file the template renders
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestFile {
+    /// Path of the file within the template
+    pub source: String,
+    /// Path to write to, relative to the target; defaults to `source`
+    pub destination: Option<String>,
+    pub description: Option<String>,
+}
+
+impl ManifestFile {
+    pub fn destination(&self) -> &str {
+        self.destination.as_deref().unwrap_or(&self.source)
+    }
+}
+
+/// A variable the template's files refer to
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Variable {
+    pub name: String,
+    pub description: Option<String>,
+    pub default: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    /// How the variable is prompted for and validated
+    #[serde(default, rename = "type")]
+    pub kind: VariableKind,
+    /// Allowed values of a `choice` variable
+    #[serde(default)]
+    pub choices: Vec<String>,
+    /// Regular expression the whole value must match
+    pub pattern: Option<String>,
+}
+
+/// Kind of value a variable holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableKind {
+    #[default]
+    Text,
+    Choice,
+    Boolean,
+}
+
+impl Variable {
+    /// Check `value` against the variable's kind, choices and pattern
+    ///
+    /// Returns the normalized value: booleans become `true` or `false`.
+    pub fn validate(&self, value: &str) -> Result<String> {
+        let invalid = |message: String| RfeError::InvalidAnswer {
+            name: self.name.clone(),
+            message,
+        };
+
+        let value = match self.kind {
+            VariableKind::Text => value.to_string(),
+            VariableKind::Boolean => match value.trim().to_lowercase().as_str() {
+                "y" | "yes" | "true" | "1" => "true".to_string(),
+                "n" | "no" | "false" | "0" => "false".to_string(),
+                _ => return Err(invalid(format!("{} is not yes or no", value))),
+            },
+            VariableKind::Choice => {
+                if !self.choices.iter().any(|choice| choice == value) {
+                    return Err(invalid(format!(
+                        "{} is not one of {}",
+                        value,
+                        self.choices.join(", ")
+                    )));
+                }
+                value.to_string()
+            }
+        };
+
+        if let Some(pattern) = &self.pattern {
+            let regex = Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| invalid(format!("invalid pattern {}: {}", pattern, e)))?;
+            if !regex.is_match(&value) {
+                return Err(invalid(format!("{} does not match {}", value, pattern)));
+            }
+        }
+        Ok(value)
+    }
+}
+
+impl Manifest {
+    /// Parse a manifest read from `source`, checking it supports this rfe
+    pub fn parse(contents: &str, source: &str) -> Result<Self> {
+        let manifest: Manifest =
+            toml::from_str(contents).map_err(|e| RfeError::InvalidManifest {
+                source: source.to_string(),
+                message: e.message().to_string(),
+            })?;
+        manifest.check_version(source)?;
+        manifest.check_paths(source)?;
+        Ok(manifest)
+    }
+
+    /// Reject file paths that would read or write outside the template or
+    /// the target directory
+    fn check_paths(&self, source: &str) -> Result<()> {
+        for file in &self.files {
+            for path in [Some(&file.source), file.destination.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                if check_relative(path).is_err() {
+                    return Err(RfeError::InvalidManifest {
+                        source: source.to_string(),
+                        message: format!("{} must be a relative path without `..`", path),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_version(&self, source: &str) -> Result<()> {
+        let required = match &self.template.min_rfe_version {
+            Some(required) => required,
+            None => return Ok(()),
+        };
+        let current = env!("CARGO_PKG_VERSION");
+        let parsed = parse_version(required).ok_or_else(|| RfeError::InvalidManifest {
+            source: source.to_string(),
+            message: format!("min_rfe_version {} is not a valid version", required),
+        })?;
+        if parse_version(current) < Some(parsed) {
+            return Err(RfeError::IncompatibleTemplate {
+                required: required.clone(),
+                current: current.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Parse `major[.minor[.patch]]`, ignoring any pre-release suffix
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.trim().trim_start_matches('v');
+    let core = core.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().transpose().ok()?.unwrap_or(0);
+    let patch = parts.next().transpose().ok()?.unwrap_or(0);
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((major, minor, patch))
+}
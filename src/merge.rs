@@ -0,0 +1,95 @@
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+mod envrc;
+mod gitignore;
+mod managed;
+mod nix;
+mod yaml;
+
+pub use envrc::merge_envrc;
+pub use gitignore::merge_gitignore;
+pub use managed::{strip_managed_block, ManagedFile, BLOCK_BEGIN, BLOCK_END};
+pub use nix::merge_devenv_nix;
+pub use yaml::merge_devenv_yaml;
+
+/// Outcome of merging a template file into an existing one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merged {
+    pub contents: String,
+    /// Human-readable descriptions of what could not be merged cleanly
+    pub conflicts: Vec<String>,
+}
+
+/// Merge the rendered template file `path` into its `existing` contents
+///
+/// Files with a known format get a structured merge; anything else falls back
+/// to `merge_lines`.
+pub fn merge(path: &str, existing: &str, template: &str) -> Merged {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    match file_name {
+        ".gitignore" => merge_gitignore(existing, template),
+        ".envrc" => merge_envrc(existing, template),
+        "devenv.yaml" => merge_devenv_yaml(existing, template),
+        "devenv.nix" => merge_devenv_nix(existing, template),
+        _ => merge_lines(existing, template),
+    }
+}
+
+/// Line-based merge of a rendered template file into its `existing` contents
+///
+/// Lines only in the existing file are kept, lines only in the template are
+/// added, and regions both sides changed are wrapped in conflict markers.
+pub fn merge_lines(existing: &str, template: &str) -> Merged {
+    let old: Vec<&str> = existing.split_inclusive('\n').collect();
+    let new: Vec<&str> = template.split_inclusive('\n').collect();
+
+    let mut contents = String::new();
+    let mut conflicts = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old, &new) {
+        match op {
+            DiffOp::Equal { old_index, len, .. } => {
+                push_lines(&mut contents, &old[old_index..old_index + len]);
+            }
+            DiffOp::Delete {
+                old_index, old_len, ..
+            } => push_lines(&mut contents, &old[old_index..old_index + old_len]),
+            DiffOp::Insert {
+                new_index, new_len, ..
+            } => push_lines(&mut contents, &new[new_index..new_index + new_len]),
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => {
+                conflicts.push(match old_len {
+                    1 => format!("line {} differs from the template", old_index + 1),
+                    _ => format!(
+                        "lines {}-{} differ from the template",
+                        old_index + 1,
+                        old_index + old_len
+                    ),
+                });
+                contents.push_str("<<<<<<< existing\n");
+                push_lines(&mut contents, &old[old_index..old_index + old_len]);
+                contents.push_str("=======\n");
+                push_lines(&mut contents, &new[new_index..new_index + new_len]);
+                contents.push_str(">>>>>>> template\n");
+            }
+        }
+    }
+    Merged {
+        contents,
+        conflicts,
+    }
+}
+
+/// Append `lines`, making sure every one ends with a newline
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use super::managed::ManagedFile;
+use super::Merged;
+use std::collections::BTreeMap;
+
+/// Merge a template `.envrc` into an existing one
+///
+/// The user's directives (`dotenv`, `PATH_add`, `source_up`, ...) are kept as
+/// they are. Template directives the user does not have yet go into the
+/// managed block; a `use` or `source_url` the user already has for the same
+/// layout or URL is not repeated, and is reported when spelled differently.
+/// Adding a `use` next to one for another layout is reported too.
+pub fn merge_envrc(existing: &str, template: &str) -> Merged {
+    let file = ManagedFile::parse(existing);
+    let mut user: BTreeMap<String, &str> = BTreeMap::new();
+    for line in file.user_lines() {
+        if let Some(key) = directive_key(line) {
+            user.entry(key).or_insert(line.trim());
+        }
+    }
+
+    let mut block = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut added = Vec::new();
+    for line in template.lines() {
+        let key = match directive_key(line) {
+            Some(key) => key,
+            None => continue,
+        };
+        match user.get(&key) {
+            Some(kept) if *kept != line.trim() => conflicts.push(format!(
+                "keeping `{}` instead of the template's `{}`",
+                kept,
+                line.trim()
+            )),
+            Some(_) => {}
+            None if added.contains(&key) => {}
+            None => {
+                if key.starts_with("use ") {
+                    let other_layouts = user.iter().filter(|(k, _)| k.starts_with("use "));
+                    for (_, other) in other_layouts {
+                        conflicts.push(format!("adding `{}` next to `{}`", line.trim(), other));
+                    }
+                }
+                block.push(line.trim());
+                added.push(key);
+            }
+        }
+    }
+    Merged {
+        contents: file.with_block(&block),
+        conflicts,
+    }
+}
+
+/// What makes two directives the same, or `None` for blanks and comments
+///
+/// `use` is keyed by its layout and `source_url` by its URL so differing
+/// arguments do not produce a second copy; other lines must match exactly,
+/// up to whitespace.
+fn directive_key(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [command @ ("use" | "source_url"), argument, ..] => {
+            Some(format!("{} {}", command, argument.trim_matches('"')))
+        }
+        _ => Some(words.join(" ")),
+    }
+}
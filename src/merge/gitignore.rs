@@ -0,0 +1,62 @@
+use super::managed::ManagedFile;
+use super::Merged;
+use std::collections::HashSet;
+
+/// Merge a template `.gitignore` into an existing one
+///
+/// Template patterns the user's lines do not already cover are kept in the
+/// managed block; the user's own lines are never changed.
+pub fn merge_gitignore(existing: &str, template: &str) -> Merged {
+    let file = ManagedFile::parse(existing);
+    let mut seen: HashSet<String> = file
+        .user_lines()
+        .filter_map(pattern)
+        .map(normalize)
+        .collect();
+    let block: Vec<&str> = template
+        .lines()
+        .filter_map(pattern)
+        .filter(|pattern| seen.insert(normalize(pattern)))
+        .collect();
+    Merged {
+        contents: file.with_block(&block),
+        conflicts: Vec::new(),
+    }
+}
+
+/// The pattern on `line`, skipping blank lines and comments
+fn pattern(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        None
+    } else {
+        Some(line)
+    }
+}
+
+/// Spell equivalent patterns the same way
+///
+/// `**/name` matches like `name` when `name` has no other slash, `dir/**`
+/// ignores the same files as `dir/`, and a leading slash is redundant when the
+/// pattern has a slash in the middle anyway.
+fn normalize(pattern: &str) -> String {
+    let (negation, pattern) = match pattern.strip_prefix('!') {
+        Some(rest) => ("!", rest),
+        None => ("", pattern),
+    };
+    let mut pattern = match pattern.strip_suffix("/**") {
+        Some(dir) => format!("{}/", dir),
+        None => pattern.to_string(),
+    };
+    let body = pattern.trim_end_matches('/');
+    if let Some(rest) = body.strip_prefix("**/") {
+        if !rest.contains('/') {
+            pattern.replace_range(..3, "");
+        }
+    } else if let Some(rest) = body.strip_prefix('/') {
+        if rest.contains('/') {
+            pattern.remove(0);
+        }
+    }
+    format!("{}{}", negation, pattern)
+}
@@ -0,0 +1,83 @@
+/// First line of the block of lines rfe maintains inside a user's file
+pub const BLOCK_BEGIN: &str = "# rfe-managed begin";
+
+/// Last line of the block of lines rfe maintains inside a user's file
+pub const BLOCK_END: &str = "# rfe-managed end";
+
+/// A line-oriented file split around its rfe-managed block
+pub struct ManagedFile<'a> {
+    before: Vec<&'a str>,
+    block: Option<Vec<&'a str>>,
+    after: Vec<&'a str>,
+}
+
+impl<'a> ManagedFile<'a> {
+    /// Split `contents` at the first managed block
+    ///
+    /// A block that is never closed runs to the end of the file.
+    pub fn parse(contents: &'a str) -> Self {
+        let lines: Vec<&str> = contents.lines().collect();
+        let begin = match lines.iter().position(|line| line.trim() == BLOCK_BEGIN) {
+            Some(begin) => begin,
+            None => {
+                return ManagedFile {
+                    before: lines,
+                    block: None,
+                    after: Vec::new(),
+                }
+            }
+        };
+        let end = lines[begin + 1..]
+            .iter()
+            .position(|line| line.trim() == BLOCK_END)
+            .map_or(lines.len(), |offset| begin + 1 + offset);
+        ManagedFile {
+            before: lines[..begin].to_vec(),
+            block: Some(lines[begin + 1..end].to_vec()),
+            after: lines.get(end + 1..).unwrap_or_default().to_vec(),
+        }
+    }
+
+    /// Lines outside the managed block, which belong to the user
+    pub fn user_lines(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.before.iter().chain(&self.after).copied()
+    }
+
+    /// Lines inside the managed block, if the file has one
+    pub fn block_lines(&self) -> Option<&[&'a str]> {
+        self.block.as_deref()
+    }
+
+    /// The file with its managed block replaced by `block`
+    ///
+    /// An existing block is updated in place; otherwise a new one is
+    /// appended after a blank line. An empty `block` removes the block.
+    pub fn with_block<S: AsRef<str>>(&self, block: &[S]) -> String {
+        let mut lines: Vec<&str> = self.before.clone();
+        if !block.is_empty() {
+            if self.block.is_none() && lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push("");
+            }
+            lines.push(BLOCK_BEGIN);
+            lines.extend(block.iter().map(AsRef::as_ref));
+            lines.push(BLOCK_END);
+        } else if self.block.is_some() {
+            // Do not leave the separator of a removed block behind
+            while self.after.is_empty() && lines.last().is_some_and(|line| line.trim().is_empty()) {
+                lines.pop();
+            }
+        }
+        lines.extend(&self.after);
+
+        let mut contents = lines.join("\n");
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+        contents
+    }
+}
+
+/// Remove the rfe-managed block from `contents`, keeping every user line
+pub fn strip_managed_block(contents: &str) -> String {
+    ManagedFile::parse(contents).with_block::<&str>(&[])
+}
@@ -0,0 +1,311 @@
+use super::{merge_lines, Merged};
+use rnix::types::{AttrSet, EntryHolder, KeyValue, List, ParsedType, TypedNode, Wrapper};
+use rnix::{SyntaxKind, SyntaxNode};
+use std::collections::BTreeMap;
+
+/// Merge a template `devenv.nix` into an existing one
+///
+/// Both files are parsed and merged attribute by attribute: `packages` lists
+/// are unioned, attributes the existing file does not set (`env.*`,
+/// `languages.*`, `services.*`, ...) are added next to its own, and
+/// attributes both files set to different values keep the user's value and
+/// are reported. Text outside the inserted attributes is left untouched.
+pub fn merge_devenv_nix(existing: &str, template: &str) -> Merged {
+    let user_ast = rnix::parse(existing);
+    let template_ast = rnix::parse(template);
+    let sets = match (body_attrset(&user_ast), body_attrset(&template_ast)) {
+        (Some(user), Some(theirs))
+            if user_ast.errors().is_empty() && template_ast.errors().is_empty() =>
+        {
+            (user, theirs)
+        }
+        _ => {
+            let mut merged = merge_lines(existing, template);
+            merged.conflicts.insert(
+                0,
+                "not an attribute set of Nix, merged line by line".to_string(),
+            );
+            return merged;
+        }
+    };
+
+    let mut merger = NixMerger {
+        existing,
+        template,
+        sets: BTreeMap::new(),
+        values: BTreeMap::new(),
+        inserts: BTreeMap::new(),
+        conflicts: Vec::new(),
+    };
+    merger.index(&sets.0, &[]);
+    merger.merge_set(&sets.1, &[]);
+
+    let mut contents = existing.to_string();
+    for (offset, text) in merger.inserts.iter().rev() {
+        contents.insert_str(*offset, text);
+    }
+    Merged {
+        contents,
+        conflicts: merger.conflicts,
+    }
+}
+
+/// The attribute set a Nix file evaluates to, looking through the function
+/// header, `let ... in` and `with`
+fn body_attrset(ast: &rnix::AST) -> Option<AttrSet> {
+    let mut node = ast.root().inner()?;
+    loop {
+        node = match ParsedType::try_from(node).ok()? {
+            ParsedType::AttrSet(set) => return Some(set),
+            ParsedType::Lambda(lambda) => lambda.body()?,
+            ParsedType::LetIn(let_in) => let_in.body()?,
+            ParsedType::With(with) => with.body()?,
+            ParsedType::Paren(paren) => paren.inner()?,
+            _ => return None,
+        };
+    }
+}
+
+/// The list `node` is, possibly behind `with pkgs;`
+fn as_list(node: &SyntaxNode) -> Option<List> {
+    match ParsedType::try_from(node.clone()).ok()? {
+        ParsedType::List(list) => Some(list),
+        ParsedType::With(with) => as_list(&with.body()?),
+        _ => None,
+    }
+}
+
+fn key_path(entry: &KeyValue) -> Option<Vec<String>> {
+    Some(
+        entry
+            .key()?
+            .path()
+            .map(|part| part.text().to_string())
+            .collect(),
+    )
+}
+
+/// Source text with runs of whitespace collapsed, for comparing values
+fn normalized(node: &SyntaxNode) -> String {
+    node.text()
+        .to_string()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Column `offset` is at in `text`
+fn column(text: &str, offset: usize) -> usize {
+    offset - text[..offset].rfind('\n').map_or(0, |newline| newline + 1)
+}
+
+fn offset(position: rnix::TextSize) -> usize {
+    usize::from(position)
+}
+
+struct NixMerger<'a> {
+    existing: &'a str,
+    template: &'a str,
+    /// Attribute sets written out in the existing file, by attribute path
+    sets: BTreeMap<Vec<String>, AttrSet>,
+    /// Every other value the existing file sets, by attribute path
+    values: BTreeMap<Vec<String>, SyntaxNode>,
+    /// Text to insert into the existing file, by byte offset
+    inserts: BTreeMap<usize, String>,
+    conflicts: Vec<String>,
+}
+
+impl NixMerger<'_> {
+    /// Record the attributes `set` at `prefix` defines in the existing file
+    fn index(&mut self, set: &AttrSet, prefix: &[String]) {
+        self.sets.insert(prefix.to_vec(), set.clone());
+        for entry in set.entries() {
+            let (Some(path), Some(value)) = (key_path(&entry), entry.value()) else {
+                continue;
+            };
+            let path = [prefix, &path].concat();
+            match AttrSet::cast(value.clone()) {
+                Some(nested) => self.index(&nested, &path),
+                None => {
+                    self.values.insert(path, value);
+                }
+            }
+        }
+    }
+
+    /// Merge the template's `set` at `prefix` into the existing file
+    fn merge_set(&mut self, set: &AttrSet, prefix: &[String]) {
+        for entry in set.entries() {
+            let (Some(path), Some(value)) = (key_path(&entry), entry.value()) else {
+                continue;
+            };
+            let path = [prefix, &path].concat();
+            let name = path.join(".");
+
+            if let Some(user_value) = self.values.get(&path).cloned() {
+                self.merge_value(&name, &user_value, &value);
+            } else if let Some(shadowing) =
+                (1..path.len()).find(|len| self.values.contains_key(&path[..*len]))
+            {
+                self.conflicts.push(format!(
+                    "{}: not added, {} is not an attribute set in the existing file",
+                    name,
+                    path[..shadowing].join(".")
+                ));
+            } else if self.sets.contains_key(&path)
+                || self
+                    .values
+                    .keys()
+                    .any(|user_path| user_path.starts_with(&path))
+            {
+                match AttrSet::cast(value.clone()) {
+                    Some(nested) => self.merge_set(&nested, &path),
+                    None => self.conflicts.push(format!(
+                        "{}: keeping the existing attributes, the template sets `{}`",
+                        name,
+                        normalized(&value)
+                    )),
+                }
+            } else {
+                self.add_entry(&entry, &path);
+            }
+        }
+    }
+
+    /// Compare a value both files set, unioning `packages` lists
+    fn merge_value(&mut self, name: &str, user_value: &SyntaxNode, value: &SyntaxNode) {
+        if normalized(user_value) == normalized(value) {
+            return;
+        }
+        if name.rsplit('.').next() == Some("packages") {
+            if let (Some(user_list), Some(list)) = (as_list(user_value), as_list(value)) {
+                self.union_lists(&user_list, &list);
+                return;
+            }
+        }
+        self.conflicts.push(format!(
+            "{}: keeping `{}`, the template sets `{}`",
+            name,
+            normalized(user_value),
+            normalized(value)
+        ));
+    }
+
+    /// Append the template's list items the existing list does not have
+    ///
+    /// `pkgs.git` and `git` (under `with pkgs;`) count as the same package.
+    fn union_lists(&mut self, user_list: &List, list: &List) {
+        let package = |item: &SyntaxNode| {
+            let text = normalized(item);
+            text.strip_prefix("pkgs.").map(String::from).unwrap_or(text)
+        };
+        let present: Vec<String> = user_list.items().map(|item| package(&item)).collect();
+        let missing: Vec<SyntaxNode> = list
+            .items()
+            .filter(|item| !present.contains(&package(item)))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let node = user_list.node();
+        let multiline = node.text().to_string().contains('\n');
+        let (at, indent) = match user_list.items().last() {
+            Some(last) => {
+                let start = offset(last.text_range().start());
+                (
+                    offset(last.text_range().end()),
+                    column(self.existing, start),
+                )
+            }
+            None => {
+                let open = offset(node.text_range().start()) + 1;
+                (open, column(self.existing, open - 1) + 2)
+            }
+        };
+        let mut text = String::new();
+        for item in missing {
+            if multiline {
+                text.push('\n');
+                text.push_str(&" ".repeat(indent));
+            } else {
+                text.push(' ');
+            }
+            text.push_str(&item.text().to_string());
+        }
+        self.inserts.entry(at).or_default().push_str(&text);
+    }
+
+    /// Copy the template's `entry` for `path` into the closest attribute set
+    /// the existing file has for it, after the last entry sharing its first
+    /// attribute name
+    fn add_entry(&mut self, entry: &KeyValue, path: &[String]) {
+        let (set_path, set) = self
+            .sets
+            .iter()
+            .filter(|(set_path, _)| path.starts_with(set_path))
+            .max_by_key(|(set_path, _)| set_path.len())
+            .map(|(set_path, set)| (set_path.clone(), set.clone()))
+            .expect("the top-level attribute set is a prefix of every path");
+
+        // Keep `languages.*` next to `languages.*` and so on, when there is one
+        let relative = &path[set_path.len()..];
+        let anchor = set
+            .entries()
+            .filter(|entry| key_path(entry).is_some_and(|key| key.first() == relative.first()))
+            .last()
+            .or_else(|| set.entries().last());
+
+        let node = set.node();
+        let (at, indent) = match anchor {
+            Some(last) => {
+                let start = offset(last.node().text_range().start());
+                (
+                    offset(last.node().text_range().end()),
+                    column(self.existing, start),
+                )
+            }
+            None => {
+                let open = node
+                    .children_with_tokens()
+                    .find(|child| child.kind() == SyntaxKind::TOKEN_CURLY_B_OPEN)
+                    .map_or(offset(node.text_range().start()), |open| {
+                        offset(open.text_range().end())
+                    });
+                (
+                    open,
+                    column(self.existing, offset(node.text_range().start())) + 2,
+                )
+            }
+        };
+
+        // Re-indent continuation lines from the template's column to ours
+        let start = offset(entry.node().text_range().start());
+        let template_indent = column(self.template, start);
+        let value = entry
+            .value()
+            .map(|value| value.text().to_string())
+            .unwrap_or_default();
+        let value = value
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| match i {
+                0 => line.to_string(),
+                _ => {
+                    let trimmed = line.trim_start_matches(' ');
+                    let depth = (line.len() - trimmed.len()).saturating_sub(template_indent);
+                    if trimmed.is_empty() {
+                        String::new()
+                    } else {
+                        format!("{}{}", " ".repeat(indent + depth), trimmed)
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let key = relative.join(".");
+        let text = format!("\n{}{} = {};", " ".repeat(indent), key, value);
+        self.inserts.entry(at).or_default().push_str(&text);
+    }
+}
@@ -0,0 +1,175 @@
+use super::{merge_lines, Merged};
+use serde_yaml::{Mapping, Value};
+
+/// Merge a template `devenv.yaml` into an existing one
+///
+/// Inputs and imports the user's file lacks are added to its `inputs` and
+/// `imports` sections; everything else in the file, comments and key order
+/// included, is left as written. An input both files declare with different
+/// URLs keeps the user's URL and is reported.
+pub fn merge_devenv_yaml(existing: &str, template: &str) -> Merged {
+    let (user, theirs) = match (
+        serde_yaml::from_str::<Value>(existing),
+        serde_yaml::from_str::<Value>(template),
+    ) {
+        (Ok(user), Ok(theirs)) => (user, theirs),
+        _ => {
+            let mut merged = merge_lines(existing, template);
+            merged
+                .conflicts
+                .insert(0, "not valid YAML, merged line by line".to_string());
+            return merged;
+        }
+    };
+
+    let mut lines: Vec<String> = existing.lines().map(String::from).collect();
+    let mut conflicts = Vec::new();
+
+    let user_inputs = user.get("inputs").and_then(Value::as_mapping);
+    let mut missing_inputs = Mapping::new();
+    for (name, input) in theirs
+        .get("inputs")
+        .and_then(Value::as_mapping)
+        .into_iter()
+        .flatten()
+    {
+        match user_inputs.and_then(|inputs| inputs.get(name)) {
+            Some(user_input) => {
+                if let (Some(ours), Some(url)) = (input_url(user_input), input_url(input)) {
+                    if ours != url {
+                        conflicts.push(format!(
+                            "input {}: keeping url {}, the template uses {}",
+                            name.as_str().unwrap_or("?"),
+                            ours,
+                            url
+                        ));
+                    }
+                }
+            }
+            None => {
+                missing_inputs.insert(name.clone(), input.clone());
+            }
+        }
+    }
+    if !missing_inputs.is_empty() {
+        add_to_section(
+            &mut lines,
+            "inputs",
+            &Value::Mapping(missing_inputs),
+            &mut conflicts,
+        );
+    }
+
+    let user_imports = user.get("imports").and_then(Value::as_sequence);
+    let missing_imports: Vec<Value> = theirs
+        .get("imports")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+        .filter(|import| !user_imports.is_some_and(|imports| imports.contains(import)))
+        .cloned()
+        .collect();
+    if !missing_imports.is_empty() {
+        add_to_section(
+            &mut lines,
+            "imports",
+            &Value::Sequence(missing_imports),
+            &mut conflicts,
+        );
+    }
+
+    let mut contents = lines.join("\n");
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+    Merged {
+        contents,
+        conflicts,
+    }
+}
+
+fn input_url(input: &Value) -> Option<&str> {
+    input.get("url").and_then(Value::as_str)
+}
+
+/// Add the entries of `value` at the end of the top-level section `key`,
+/// creating the section at the end of the file when there is none
+fn add_to_section(lines: &mut Vec<String>, key: &str, value: &Value, conflicts: &mut Vec<String>) {
+    let entries = match serde_yaml::to_string(value) {
+        Ok(entries) => entries,
+        Err(e) => {
+            conflicts.push(format!("{}: {}", key, e));
+            return;
+        }
+    };
+
+    let start = match lines
+        .iter()
+        .position(|line| section_value(line, key).is_some())
+    {
+        Some(start) => start,
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("{}:", key));
+            lines.extend(entries.lines().map(|entry| format!("  {}", entry)));
+            return;
+        }
+    };
+
+    match section_value(&lines[start], key).unwrap_or_default() {
+        "" => {}
+        "{}" | "[]" | "~" | "null" => lines[start] = format!("{}:", key),
+        _ => {
+            conflicts.push(format!(
+                "{} is written inline, add {} by hand",
+                key,
+                entries.trim_end().replace('\n', " ")
+            ));
+            return;
+        }
+    }
+
+    // The section runs until the next top-level key; a sequence's items may
+    // sit at the same indentation as the key itself
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| is_content(line) && indent(line) == 0 && !line.starts_with('-'))
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    let children: Vec<usize> = (start + 1..end)
+        .filter(|&i| is_content(&lines[i]))
+        .collect();
+    let child_indent = match children.first() {
+        Some(&first) => indent(&lines[first]),
+        None => 2,
+    };
+    let insert_at = children.last().map_or(start + 1, |last| last + 1);
+
+    let indented = entries
+        .lines()
+        .map(|entry| format!("{}{}", " ".repeat(child_indent), entry));
+    lines.splice(insert_at..insert_at, indented);
+}
+
+/// The value written after `key:` when `line` is that top-level key
+fn section_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(key)?.strip_prefix(':')?;
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let value = match rest.find(" #") {
+        Some(comment) => &rest[..comment],
+        None => rest,
+    };
+    Some(value.trim())
+}
+
+fn is_content(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && !line.starts_with('#')
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
@@ -0,0 +1,281 @@
+use crate::error::{Result, RfeError};
+use crate::merge::{self, Merged};
+use crate::scaffold::RenderedFile;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Suffix of the copy `backup` keeps of a file before replacing it
+pub const BACKUP_SUFFIX: &str = ".orig";
+
+/// What applying a plan does to one file in the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileAction {
+    /// The file does not exist yet
+    Create,
+    /// The existing file is replaced by the rendered one
+    Overwrite,
+    /// The existing file is left alone
+    Skip,
+    /// The existing file is copied to `<file>.orig` (or the next free
+    /// `<file>.orig.N`), then replaced
+    Backup,
+    /// The existing file is combined with the rendered one
+    Merge,
+}
+
+impl fmt::Display for FileAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FileAction::Create => "create",
+            FileAction::Overwrite => "overwrite",
+            FileAction::Skip => "skip",
+            FileAction::Backup => "backup",
+            FileAction::Merge => "merge",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How files that already exist in the target with other contents are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    Skip,
+    #[default]
+    Overwrite,
+    Backup,
+    Merge,
+    /// Ask for each file
+    Prompt,
+}
+
+impl ConflictPolicy {
+    /// Names accepted by `parse`, as given to `--on-conflict`
+    pub const NAMES: [&'static str; 5] = ["skip", "overwrite", "backup", "merge", "prompt"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(ConflictPolicy::Skip),
+            "overwrite" => Some(ConflictPolicy::Overwrite),
+            "backup" => Some(ConflictPolicy::Backup),
+            "merge" => Some(ConflictPolicy::Merge),
+            "prompt" => Some(ConflictPolicy::Prompt),
+            _ => None,
+        }
+    }
+
+    /// Action taken for every conflicting file, `None` when asking per file
+    pub fn action(self) -> Option<FileAction> {
+        match self {
+            ConflictPolicy::Skip => Some(FileAction::Skip),
+            ConflictPolicy::Overwrite => Some(FileAction::Overwrite),
+            ConflictPolicy::Backup => Some(FileAction::Backup),
+            ConflictPolicy::Merge => Some(FileAction::Merge),
+            ConflictPolicy::Prompt => None,
+        }
+    }
+}
+
+/// A file in the plan together with what is currently on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedFile {
+    /// Destination path relative to the target directory
+    pub path: String,
+    pub action: FileAction,
+    /// Rendered contents of the template file
+    pub contents: String,
+    /// Contents currently in the target, if the file exists
+    pub existing: Option<String>,
+}
+
+impl PlannedFile {
+    /// Plan `file` against what is on disk below `target`
+    ///
+    /// Missing files are created, differing files are overwritten and
+    /// identical files are skipped.
+    fn compute(target: &Path, file: RenderedFile) -> Result<Self> {
+        let existing = read_existing(&target.join(&file.path))?;
+        let action = match &existing {
+            None => FileAction::Create,
+            Some(existing) if *existing == file.contents => FileAction::Skip,
+            Some(_) => FileAction::Overwrite,
+        };
+        Ok(PlannedFile {
+            path: file.path,
+            action,
+            contents: file.contents,
+            existing,
+        })
+    }
+
+    /// Whether the file exists in the target with different contents
+    pub fn is_conflict(&self) -> bool {
+        matches!(&self.existing, Some(existing) if *existing != self.contents)
+    }
+
+    /// The existing file merged with the template, for the `merge` action
+    pub fn merged(&self) -> Option<Merged> {
+        match (self.action, &self.existing) {
+            (FileAction::Merge, Some(existing)) => {
+                Some(merge::merge(&self.path, existing, &self.contents))
+            }
+            _ => None,
+        }
+    }
+
+    /// Contents the file will have after applying the plan
+    pub fn result(&self) -> String {
+        match (self.action, &self.existing) {
+            (FileAction::Skip, Some(existing)) => existing.clone(),
+            (FileAction::Merge, Some(_)) => self.merged().map(|m| m.contents).unwrap_or_default(),
+            _ => self.contents.clone(),
+        }
+    }
+
+    /// Whether applying the plan changes this file on disk
+    pub fn changes(&self) -> bool {
+        self.action != FileAction::Skip && self.existing.as_deref() != Some(&self.result())
+    }
+
+    /// Unified diff from the current contents to the planned ones
+    ///
+    /// Empty when the file does not change.
+    pub fn diff(&self) -> String {
+        if !self.changes() {
+            return String::new();
+        }
+        unified_diff(&self.path, self.existing.as_deref(), &self.result())
+    }
+}
+
+/// Unified diff of the file at `path` from `old`, `None` when it does not
+/// exist yet, to `new`
+pub(crate) fn unified_diff(path: &str, old: Option<&str>, new: &str) -> String {
+    let old_header = match old {
+        Some(_) => format!("a/{}", path),
+        None => "/dev/null".to_string(),
+    };
+    TextDiff::from_lines(old.unwrap_or(""), new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&old_header, &format!("b/{}", path))
+        .to_string()
+}
+
+/// The set of file operations that will be applied under a target directory
+#[derive(Debug, Clone)]
+pub struct WritePlan {
+    target: PathBuf,
+    files: Vec<PlannedFile>,
+}
+
+impl WritePlan {
+    /// Plan writing `files` under `target`, inspecting what already exists
+    pub fn new(target: impl Into<PathBuf>, files: Vec<RenderedFile>) -> Result<Self> {
+        let target = target.into();
+        let files = files
+            .into_iter()
+            .map(|file| PlannedFile::compute(&target, file))
+            .collect::<Result<_>>()?;
+        Ok(WritePlan { target, files })
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    pub fn files(&self) -> &[PlannedFile] {
+        &self.files
+    }
+
+    /// Choose the action of every conflicting file with `decide`
+    pub fn resolve_conflicts(
+        &mut self,
+        mut decide: impl FnMut(&PlannedFile) -> Result<FileAction>,
+    ) -> Result<()> {
+        for file in self.files.iter_mut().filter(|file| file.is_conflict()) {
+            file.action = decide(file)?;
+        }
+        Ok(())
+    }
+
+    /// Write every file the plan changes, creating directories as needed
+    ///
+    /// Files planned for `backup` are first copied to `<file>.orig`. An
+    /// existing backup is never replaced: later copies go to `<file>.orig.1`,
+    /// `<file>.orig.2` and so on, so `.orig` always holds the file from
+    /// before rfe first touched it. Returns the paths that were written, in
+    /// plan order.
+    pub fn apply(&self) -> Result<Vec<PathBuf>> {
+        let mut written = Vec::new();
+        for file in &self.files {
+            check_relative(&file.path)?;
+        }
+        for file in self.files.iter().filter(|file| file.changes()) {
+            let file_path = self.target.join(&file.path);
+
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent).map_err(|e| RfeError::io(parent, e))?;
+            }
+            if let (FileAction::Backup, Some(_)) = (file.action, &file.existing) {
+                // Copy the bytes on disk; `existing` is lossily decoded
+                let backup_path = free_backup_path(&file_path);
+                fs::copy(&file_path, &backup_path).map_err(|e| RfeError::io(&backup_path, e))?;
+                written.push(backup_path);
+            }
+            fs::write(&file_path, file.result()).map_err(|e| RfeError::io(&file_path, e))?;
+            written.push(file_path);
+        }
+        Ok(written)
+    }
+}
+
+/// Check that `path` stays below the directory it is joined to: it must be
+/// relative, non-empty and free of `..` components
+pub(crate) fn check_relative(path: &str) -> Result<()> {
+    let escapes = Path::new(path)
+        .components()
+        .any(|part| !matches!(part, Component::Normal(_) | Component::CurDir));
+    if path.is_empty() || escapes {
+        return Err(RfeError::UnsafePath {
+            path: path.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Contents of the file at `path`, `None` when there is none
+pub(crate) fn read_existing(path: &Path) -> Result<Option<String>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(String::from_utf8_lossy(&bytes).into_owned())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(RfeError::io(path, e)),
+    }
+}
+
+/// Path of the backup `backup` keeps of `path`
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(BACKUP_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// `backup_path`, or the first `<file>.orig.N` not taken when it exists
+fn free_backup_path(path: &Path) -> PathBuf {
+    let first = backup_path(path);
+    if !first.exists() {
+        return first;
+    }
+    (1..)
+        .map(|n| {
+            let mut name = first.as_os_str().to_owned();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .expect("a backup number is free")
+}
@@ -0,0 +1,114 @@
+use crate::error::{Result, RfeError};
+use crate::manifest::{Manifest, Variable, VariableKind};
+use crate::plan::{FileAction, PlannedFile};
+use crate::template::Variables;
+use std::io::{self, BufRead, Write};
+
+/// Asks for template variables and conflict resolutions on a line-based
+/// terminal
+pub struct Prompter<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Prompter { input, output }
+    }
+
+    /// Ask for every variable in `manifest` that `known` has no value for
+    ///
+    /// Returns only the newly answered variables.
+    pub fn ask_missing(&mut self, manifest: &Manifest, known: &Variables) -> Result<Variables> {
+        let mut answers = Variables::new();
+        for variable in &manifest.variables {
+            if known.get(&variable.name).is_some() {
+                continue;
+            }
+            if let Some(value) = self.ask(variable)? {
+                answers.set(&variable.name, value);
+            }
+        }
+        Ok(answers)
+    }
+
+    /// Ask for one variable until a valid value is given
+    ///
+    /// An empty answer picks the default; for optional variables without a
+    /// default it leaves the variable unset.
+    pub fn ask(&mut self, variable: &Variable) -> Result<Option<String>> {
+        loop {
+            let line = self.read_answer(variable)?;
+            let answer = match (line.as_str(), &variable.default) {
+                ("", Some(default)) => default.clone(),
+                ("", None) if !variable.required => return Ok(None),
+                ("", None) => {
+                    self.say(&format!("{} is required", variable.name))?;
+                    continue;
+                }
+                (answer, _) => answer.to_string(),
+            };
+            match variable.validate(&answer) {
+                Ok(value) => return Ok(Some(value)),
+                Err(RfeError::InvalidAnswer { message, .. }) => self.say(&message)?,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Show the diff overwriting `file` would apply and ask what to do with it
+    ///
+    /// An empty answer skips the file.
+    pub fn choose_action(&mut self, file: &PlannedFile) -> Result<FileAction> {
+        write!(self.output, "{}", file.diff()).map_err(stdio_error)?;
+        loop {
+            let prompt = format!(
+                "{} exists and differs: [o]verwrite, [s]kip, [b]ackup, [m]erge? [s]",
+                file.path
+            );
+            match self.read_line(&prompt)?.to_lowercase().as_str() {
+                "o" | "overwrite" => return Ok(FileAction::Overwrite),
+                "" | "s" | "skip" => return Ok(FileAction::Skip),
+                "b" | "backup" => return Ok(FileAction::Backup),
+                "m" | "merge" => return Ok(FileAction::Merge),
+                answer => self.say(&format!("{} is not one of o, s, b, m", answer))?,
+            }
+        }
+    }
+
+    fn read_answer(&mut self, variable: &Variable) -> Result<String> {
+        let label = variable.description.as_deref().unwrap_or(&variable.name);
+        let hint = match variable.kind {
+            VariableKind::Text => String::new(),
+            VariableKind::Choice => format!(" ({})", variable.choices.join("/")),
+            VariableKind::Boolean => " (y/n)".to_string(),
+        };
+        let default = match &variable.default {
+            Some(default) => format!(" [{}]", default),
+            None => String::new(),
+        };
+        self.read_line(&format!("{}{}{}", label, hint, default))
+    }
+
+    fn read_line(&mut self, prompt: &str) -> Result<String> {
+        write!(self.output, "{}: ", prompt).map_err(stdio_error)?;
+        self.output.flush().map_err(stdio_error)?;
+
+        let mut line = String::new();
+        if self.input.read_line(&mut line).map_err(stdio_error)? == 0 {
+            return Err(stdio_error(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "no answer given",
+            )));
+        }
+        Ok(line.trim().to_string())
+    }
+
+    fn say(&mut self, message: &str) -> Result<()> {
+        writeln!(self.output, "  {}", message).map_err(stdio_error)
+    }
+}
+
+fn stdio_error(error: io::Error) -> RfeError {
+    RfeError::io("<terminal>", error)
+}
@@ -0,0 +1,101 @@
+use crate::error::Result;
+use crate::plan::{check_relative, WritePlan};
+use crate::stuff::{Provenance, SourceContentReader};
+use crate::template::{self, Variables};
+use std::path::{Path, PathBuf};
+
+/// Files written by `rfe init`, relative to the target directory
+pub const SCAFFOLD_FILES: [&str; 4] = ["devenv.yaml", "devenv.nix", ".gitignore", ".envrc"];
+
+/// A scaffold file resolved from a source, ready to be written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedFile {
+    /// Destination path relative to the target directory
+    pub path: String,
+    pub contents: String,
+}
+
+/// Resolve every scaffold file through `reader` and render it with `variables`
+///
+/// When the source has an `rfe.toml` listing files, those are rendered to
+/// their declared destinations; otherwise `SCAFFOLD_FILES` are used.
+/// Destination paths are templates too.
+pub fn render_scaffold(
+    reader: &SourceContentReader,
+    variables: &Variables,
+) -> Result<Vec<RenderedFile>> {
+    scaffold_files(reader)?
+        .into_iter()
+        .map(|(source, destination)| {
+            let contents = reader.read_file_contents(&source)?;
+            Ok(RenderedFile {
+                path: render_destination(&destination, variables)?,
+                contents: template::render(&source, &contents, variables)?,
+            })
+        })
+        .collect()
+}
+
+/// Render the one file `render_scaffold` writes to `path`, reporting where
+/// its template was found
+///
+/// `path` may also name the file within the template. Files the scaffold
+/// does not list are read from the source as they are and rendered too.
+pub fn render_file(
+    reader: &SourceContentReader,
+    variables: &Variables,
+    path: &str,
+) -> Result<(RenderedFile, Provenance)> {
+    let mut found = None;
+    for (source, destination) in scaffold_files(reader)? {
+        let destination = render_destination(&destination, variables)?;
+        if destination == path || source == path {
+            found = Some((source, destination));
+            break;
+        }
+    }
+    let (source, destination) = found.unwrap_or_else(|| (path.to_string(), path.to_string()));
+    check_relative(&destination)?;
+
+    let (contents, provenance) = reader.read_file(&source)?;
+    let file = RenderedFile {
+        path: destination,
+        contents: template::render(&source, &contents, variables)?,
+    };
+    Ok((file, provenance))
+}
+
+/// Render a destination path, which must stay inside the target
+///
+/// Checked after rendering too, as variables can produce `..` or a leading `/`.
+fn render_destination(destination: &str, variables: &Variables) -> Result<String> {
+    let path = template::render(destination, destination, variables)?;
+    check_relative(&path)?;
+    Ok(path)
+}
+
+/// Template path and destination of every file the scaffold renders
+fn scaffold_files(reader: &SourceContentReader) -> Result<Vec<(String, String)>> {
+    Ok(match reader.manifest()? {
+        Some(manifest) if !manifest.files.is_empty() => manifest
+            .files
+            .iter()
+            .map(|file| (file.source.clone(), file.destination().to_string()))
+            .collect(),
+        _ => SCAFFOLD_FILES
+            .iter()
+            .map(|filename| (filename.to_string(), filename.to_string()))
+            .collect(),
+    })
+}
+
+/// Render every scaffold file through `reader` and write it under `target`
+///
+/// Returns the paths that were written, in render order.
+pub fn write_scaffold(
+    reader: &SourceContentReader,
+    variables: &Variables,
+    target: &Path,
+) -> Result<Vec<PathBuf>> {
+    WritePlan::new(target, render_scaffold(reader, variables)?)?.apply()
+}
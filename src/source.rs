@@ -0,0 +1,32 @@
+use crate::error::Result;
+
+mod embedded;
+mod git;
+mod git_url;
+mod local;
+mod subdir;
+
+pub use embedded::EmbeddedSource;
+pub use git::GitSource;
+pub use git_url::{GitTransport, GitUrl};
+pub use local::LocalDirectorySource;
+pub use subdir::SubdirSource;
+
+/// A backend that template files can be read from
+///
+/// Paths are relative to the root of the template and use `/` as separator.
+pub trait TemplateSource {
+    /// List every file the source offers
+    fn list_files(&self) -> Result<Vec<String>>;
+
+    /// Read a file, returning `None` when the source does not have it
+    fn read_bytes(&self, path: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Human readable description of where the files come from
+    fn describe(&self) -> String;
+
+    /// Commit id the files are read at, for versioned sources
+    fn revision(&self) -> Option<String> {
+        None
+    }
+}
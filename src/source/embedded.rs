@@ -0,0 +1,37 @@
+use super::TemplateSource;
+use crate::error::Result;
+use include_dir::{include_dir, Dir};
+
+static PROJECT_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/init");
+
+/// The scaffold embedded into the binary at build time
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EmbeddedSource;
+
+impl EmbeddedSource {
+    fn collect(dir: &Dir, files: &mut Vec<String>) {
+        for file in dir.files() {
+            files.push(file.path().to_string_lossy().replace('\\', "/"));
+        }
+        for sub in dir.dirs() {
+            Self::collect(sub, files);
+        }
+    }
+}
+
+impl TemplateSource for EmbeddedSource {
+    fn list_files(&self) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        Self::collect(&PROJECT_DIR, &mut files);
+        files.sort();
+        Ok(files)
+    }
+
+    fn read_bytes(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        Ok(PROJECT_DIR.get_file(path).map(|f| f.contents().to_vec()))
+    }
+
+    fn describe(&self) -> String {
+        "embedded scaffold".to_string()
+    }
+}
@@ -0,0 +1,296 @@
+use super::local::{read_below, walk};
+use super::TemplateSource;
+use crate::cache::Cache;
+use crate::error::{Result, RfeError};
+use git2::{
+    Config, Cred, CredentialType, Direction, FetchOptions, ObjectType, Oid, RemoteCallbacks,
+    Repository, StatusOptions, TreeWalkMode, TreeWalkResult,
+};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Refspecs fetched into the bare repository backing a remote source
+const FETCH_REFSPECS: [&str; 2] = [
+    "+refs/heads/*:refs/remotes/origin/*",
+    "+refs/tags/*:refs/tags/*",
+];
+
+/// Symbolic ref pointing at the remote's default branch
+const ORIGIN_HEAD: &str = "refs/remotes/origin/HEAD";
+
+/// Where a `GitSource` reads file contents from
+enum Contents {
+    /// The checked-out working tree, including uncommitted edits
+    WorkingTree(PathBuf),
+    /// The tree of a commit, read straight from the object database
+    Commit { repo: Repository, commit: Oid },
+}
+
+/// Template files read from a git repository
+pub struct GitSource {
+    url: String,
+    contents: Contents,
+    commit: Option<String>,
+    // Keeps a temporary bare repository alive for as long as the source is in use
+    _temp_dir: Option<TempDir>,
+}
+
+impl GitSource {
+    /// Use the working tree of a git repository already on disk
+    ///
+    /// The revision is HEAD only while the working tree is clean; with
+    /// uncommitted or untracked files the contents are in no commit, so there
+    /// is none.
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        let location = path.into();
+        let commit = Repository::open(&location)
+            .ok()
+            .filter(is_clean)
+            .and_then(|repo| Some(repo.head().ok()?.peel_to_commit().ok()?.id().to_string()));
+        GitSource {
+            url: location.display().to_string(),
+            contents: Contents::WorkingTree(location),
+            commit,
+            _temp_dir: None,
+        }
+    }
+
+    /// Read a git repository on disk at a committed branch, tag or commit,
+    /// ignoring its working tree
+    pub fn local(path: &Path, git_ref: &str) -> Result<Self> {
+        let url = path.display().to_string();
+        let repo = Repository::open(path).map_err(|error| RfeError::Git {
+            url: url.clone(),
+            error,
+        })?;
+        let candidates = [
+            format!("refs/heads/{}", git_ref),
+            format!("refs/tags/{}", git_ref),
+            format!("refs/remotes/origin/{}", git_ref),
+            git_ref.to_string(),
+        ];
+        let commit = resolve_ref(&repo, &candidates).ok_or_else(|| RfeError::RefNotFound {
+            reference: git_ref.to_string(),
+            source: url.clone(),
+        })?;
+        Ok(Self::at_commit(url, repo, commit, None))
+    }
+
+    /// Fetch a repository into a temporary bare repository
+    ///
+    /// When `git_ref` is given files are read at that branch, tag or commit;
+    /// otherwise the remote's default branch is used.
+    pub fn remote(url: &str, git_ref: Option<&str>) -> Result<Self> {
+        let temp_dir = tempfile::tempdir().map_err(|e| RfeError::io(std::env::temp_dir(), e))?;
+        let repo = Repository::init_bare(temp_dir.path()).map_err(|e| clone_failed(url, e))?;
+        fetch(&repo, url).map_err(|e| clone_failed(url, e))?;
+
+        let commit = resolve_fetched(&repo, url, git_ref)?;
+        Ok(Self::at_commit(
+            url.to_string(),
+            repo,
+            commit,
+            Some(temp_dir),
+        ))
+    }
+
+    /// Read a repository through the on-disk `cache`
+    ///
+    /// The cached bare repository is created or incrementally fetched, unless
+    /// `offline` is set in which case it is used as is.
+    pub fn cached(cache: &Cache, url: &str, git_ref: Option<&str>, offline: bool) -> Result<Self> {
+        let path = cache.path_for(url);
+        let repo = if path.exists() {
+            let repo = Repository::open_bare(&path).map_err(|e| clone_failed(url, e))?;
+            if !offline {
+                fetch(&repo, url).map_err(|e| clone_failed(url, e))?;
+            }
+            repo
+        } else if offline {
+            return Err(RfeError::NotCached {
+                url: url.to_string(),
+            });
+        } else {
+            let repo = Repository::init_bare(&path).map_err(|e| clone_failed(url, e))?;
+            if let Err(e) = fetch(&repo, url) {
+                // Do not leave a half-initialised entry behind
+                let _ = std::fs::remove_dir_all(&path);
+                return Err(clone_failed(url, e));
+            }
+            repo
+        };
+        cache.touch(&path)?;
+
+        let commit = resolve_fetched(&repo, url, git_ref)?;
+        Ok(Self::at_commit(url.to_string(), repo, commit, None))
+    }
+
+    fn at_commit(url: String, repo: Repository, commit: Oid, temp_dir: Option<TempDir>) -> Self {
+        GitSource {
+            url,
+            contents: Contents::Commit { repo, commit },
+            commit: Some(commit.to_string()),
+            _temp_dir: temp_dir,
+        }
+    }
+
+    fn git_error(&self, error: git2::Error) -> RfeError {
+        RfeError::Git {
+            url: self.url.clone(),
+            error,
+        }
+    }
+}
+
+fn clone_failed(url: &str, error: git2::Error) -> RfeError {
+    RfeError::CloneFailed {
+        url: url.to_string(),
+        error,
+    }
+}
+
+/// Whether the working tree of `repo` has no uncommitted or untracked files
+fn is_clean(repo: &Repository) -> bool {
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).include_ignored(false);
+    repo.statuses(Some(&mut options))
+        .is_ok_and(|statuses| statuses.is_empty())
+}
+
+/// Fetch branches and tags from `url` into the `origin` remote, recording the
+/// remote's default branch as `refs/remotes/origin/HEAD`
+fn fetch(repo: &Repository, url: &str) -> std::result::Result<(), git2::Error> {
+    let mut remote = match repo.find_remote("origin") {
+        Ok(remote) if remote.url() == Some(url) => remote,
+        Ok(_) => {
+            repo.remote_set_url("origin", url)?;
+            repo.find_remote("origin")?
+        }
+        Err(_) => repo.remote("origin", url)?,
+    };
+    let default_branch = remote
+        .connect_auth(Direction::Fetch, Some(remote_callbacks()), None)?
+        .default_branch()?;
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(remote_callbacks());
+    remote.fetch(&FETCH_REFSPECS, Some(&mut options), None)?;
+
+    let default_branch = default_branch.as_str().unwrap_or("refs/heads/main");
+    let target = default_branch.replacen("refs/heads/", "refs/remotes/origin/", 1);
+    repo.reference_symbolic(ORIGIN_HEAD, &target, true, "rfe: default branch")?;
+    Ok(())
+}
+
+/// Callbacks answering authentication requests the way git does
+///
+/// SSH keys come from the ssh-agent; passwords and tokens come from the
+/// configured credential helper. Each kind is tried once, so a rejected
+/// credential fails the fetch instead of being offered again.
+fn remote_callbacks<'a>() -> RemoteCallbacks<'a> {
+    let mut tried = CredentialType::empty();
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        let mut attempt = |kind: CredentialType| {
+            let first = allowed.contains(kind) && !tried.contains(kind);
+            if first {
+                tried |= kind;
+            }
+            first
+        };
+        // scp-like URLs without a user log in as `git`, like the forges expect
+        let ssh_user = username.unwrap_or("git");
+        if attempt(CredentialType::USERNAME) {
+            Cred::username(ssh_user)
+        } else if attempt(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(ssh_user)
+        } else if attempt(CredentialType::USER_PASS_PLAINTEXT) {
+            Cred::credential_helper(&Config::open_default()?, url, username)
+        } else if attempt(CredentialType::DEFAULT) {
+            Cred::default()
+        } else {
+            Err(git2::Error::from_str(
+                "no usable credentials for the remote",
+            ))
+        }
+    });
+    callbacks
+}
+
+/// Resolve `git_ref`, or the default branch, in a fetched bare repository
+fn resolve_fetched(repo: &Repository, url: &str, git_ref: Option<&str>) -> Result<Oid> {
+    let candidates = match git_ref {
+        Some(git_ref) => vec![
+            format!("refs/remotes/origin/{}", git_ref),
+            format!("refs/tags/{}", git_ref),
+            git_ref.to_string(),
+        ],
+        None => vec![ORIGIN_HEAD.to_string()],
+    };
+    resolve_ref(repo, &candidates).ok_or_else(|| RfeError::RefNotFound {
+        reference: git_ref.unwrap_or("HEAD").to_string(),
+        source: url.to_string(),
+    })
+}
+
+/// Resolve the first of `candidates` that names a commit
+fn resolve_ref(repo: &Repository, candidates: &[String]) -> Option<Oid> {
+    candidates
+        .iter()
+        .find_map(|spec| Some(repo.revparse_single(spec).ok()?.peel_to_commit().ok()?.id()))
+}
+
+impl TemplateSource for GitSource {
+    fn list_files(&self) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        match &self.contents {
+            Contents::Commit { repo, commit } => {
+                let tree = repo
+                    .find_commit(*commit)
+                    .and_then(|commit| commit.tree())
+                    .map_err(|e| self.git_error(e))?;
+                tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+                    if entry.kind() == Some(ObjectType::Blob) {
+                        if let Some(name) = entry.name() {
+                            files.push(format!("{}{}", root, name));
+                        }
+                    }
+                    TreeWalkResult::Ok
+                })
+                .map_err(|e| self.git_error(e))?;
+            }
+            Contents::WorkingTree(location) => walk(location, location, &mut files)?,
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn read_bytes(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let (repo, commit) = match &self.contents {
+            Contents::WorkingTree(location) => return read_below(location, path),
+            Contents::Commit { repo, commit } => (repo, *commit),
+        };
+
+        let tree = repo
+            .find_commit(commit)
+            .and_then(|commit| commit.tree())
+            .map_err(|e| self.git_error(e))?;
+        let entry = match tree.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(self.git_error(e)),
+        };
+        if entry.kind() != Some(ObjectType::Blob) {
+            return Ok(None);
+        }
+        let blob = repo.find_blob(entry.id()).map_err(|e| self.git_error(e))?;
+        Ok(Some(blob.content().to_vec()))
+    }
+
+    fn describe(&self) -> String {
+        format!("git repository {}", self.url)
+    }
+
+    fn revision(&self) -> Option<String> {
+        self.commit.clone()
+    }
+}
@@ -0,0 +1,97 @@
+use url::Url;
+
+/// Transport a git URL is fetched over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitTransport {
+    Https,
+    Http,
+    Ssh,
+    Git,
+    File,
+}
+
+/// A remote git repository location
+///
+/// Accepts `https://`, `http://`, `ssh://`, `git://` and `file://` URLs as
+/// well as scp-like `[user@]host:path` syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitUrl {
+    pub transport: GitTransport,
+    /// Lowercased host name, `None` for `file://` URLs
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    /// Repository path, without leading or trailing slashes
+    pub path: String,
+}
+
+impl GitUrl {
+    pub fn parse(s: &str) -> Option<Self> {
+        // `host:path` also parses as a URL whose scheme is the host name
+        match Url::parse(s) {
+            Ok(url) => match Self::transport(url.scheme()) {
+                Some(transport) => Self::from_url(&url, transport),
+                None => Self::parse_scp(s),
+            },
+            Err(_) => Self::parse_scp(s),
+        }
+    }
+
+    fn transport(scheme: &str) -> Option<GitTransport> {
+        match scheme {
+            "https" => Some(GitTransport::Https),
+            "http" => Some(GitTransport::Http),
+            "ssh" | "git+ssh" | "ssh+git" => Some(GitTransport::Ssh),
+            "git" => Some(GitTransport::Git),
+            "file" => Some(GitTransport::File),
+            _ => None,
+        }
+    }
+
+    fn from_url(url: &Url, transport: GitTransport) -> Option<Self> {
+        let host = url.host_str().map(str::to_lowercase);
+        if host.is_none() && transport != GitTransport::File {
+            return None;
+        }
+        let path = url.path().trim_matches('/').to_string();
+        if path.is_empty() {
+            return None;
+        }
+        Some(GitUrl {
+            transport,
+            host,
+            port: url.port(),
+            path,
+        })
+    }
+
+    /// Parse scp-like `[user@]host:path`, as understood by git itself
+    fn parse_scp(s: &str) -> Option<Self> {
+        let (authority, path) = s.split_once(':')?;
+        let host = authority.rsplit('@').next()?;
+        // A slash before the colon means a local path, as does a drive letter
+        if authority.contains('/') || host.len() < 2 || path.starts_with("//") {
+            return None;
+        }
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            return None;
+        }
+        Some(GitUrl {
+            transport: GitTransport::Ssh,
+            host: Some(host.to_lowercase()),
+            port: None,
+            path: path.to_string(),
+        })
+    }
+
+    /// Transport-independent identity of the repository: host, port and path
+    /// without a trailing `.git`
+    pub fn normalized(&self) -> String {
+        let path = self.path.strip_suffix(".git").unwrap_or(&self.path);
+        match (&self.host, self.port) {
+            (Some(host), Some(port)) => format!("{}:{}/{}", host, port, path),
+            (Some(host), None) => format!("{}/{}", host, path),
+            (None, _) => format!("/{}", path),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use super::TemplateSource;
+use crate::error::{Result, RfeError};
+use crate::plan::check_relative;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Template files read from a directory on disk
+#[derive(Debug, Clone)]
+pub struct LocalDirectorySource {
+    root: PathBuf,
+}
+
+impl LocalDirectorySource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalDirectorySource { root: root.into() }
+    }
+}
+
+/// Recursively collect files below `dir`, skipping `.git`
+pub(crate) fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(|e| RfeError::io(dir, e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| RfeError::io(dir, e))?;
+        let path = entry.path();
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        if path.is_dir() {
+            walk(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(())
+}
+
+/// Read `path` below `root`, returning `None` when it does not exist
+///
+/// `path` must be relative and free of `..`, so reads cannot leave `root`.
+pub(crate) fn read_below(root: &Path, path: &str) -> Result<Option<Vec<u8>>> {
+    check_relative(path)?;
+    let file_path = root.join(path);
+    if !file_path.is_file() {
+        return Ok(None);
+    }
+    fs::read(&file_path)
+        .map(Some)
+        .map_err(|e| RfeError::io(&file_path, e))
+}
+
+impl TemplateSource for LocalDirectorySource {
+    fn list_files(&self) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        walk(&self.root, &self.root, &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
+    fn read_bytes(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        read_below(&self.root, path)
+    }
+
+    fn describe(&self) -> String {
+        format!("local directory {}", self.root.display())
+    }
+}
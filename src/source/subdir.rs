@@ -0,0 +1,48 @@
+use super::TemplateSource;
+use crate::error::Result;
+use crate::plan::check_relative;
+
+/// Roots another source at one of its subdirectories
+pub struct SubdirSource {
+    inner: Box<dyn TemplateSource>,
+    prefix: String,
+}
+
+impl SubdirSource {
+    /// Root `inner` at `subdir`, which must be relative and free of `..`
+    pub fn new(inner: Box<dyn TemplateSource>, subdir: &str) -> Result<Self> {
+        check_relative(subdir)?;
+        Ok(SubdirSource {
+            inner,
+            prefix: format!("{}/", subdir.trim_matches('/')),
+        })
+    }
+
+    /// The subdirectory, without a trailing slash
+    pub fn subdir(&self) -> &str {
+        self.prefix.trim_end_matches('/')
+    }
+}
+
+impl TemplateSource for SubdirSource {
+    fn list_files(&self) -> Result<Vec<String>> {
+        Ok(self
+            .inner
+            .list_files()?
+            .into_iter()
+            .filter_map(|path| path.strip_prefix(&self.prefix).map(String::from))
+            .collect())
+    }
+
+    fn read_bytes(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.read_bytes(&format!("{}{}", self.prefix, path))
+    }
+
+    fn describe(&self) -> String {
+        format!("{} (subdirectory {})", self.inner.describe(), self.subdir())
+    }
+
+    fn revision(&self) -> Option<String> {
+        self.inner.revision()
+    }
+}
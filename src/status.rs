@@ -0,0 +1,103 @@
+use crate::error::Result;
+use crate::lock::{content_hash, Lockfile};
+use crate::plan::read_existing;
+use crate::scaffold::RenderedFile;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+/// How a managed file compares with what rfe wrote and what the template
+/// renders now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileState {
+    /// The file is as rfe left it and the template still renders the same
+    Unmodified,
+    /// The file was edited since rfe wrote it
+    Modified,
+    /// The file was deleted since rfe wrote it
+    Missing,
+    /// The file is as rfe left it but the template has changed; `update`
+    /// would change it
+    Outdated,
+}
+
+impl fmt::Display for FileState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FileState::Unmodified => "unmodified",
+            FileState::Modified => "modified",
+            FileState::Missing => "missing",
+            FileState::Outdated => "outdated",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The state of one file in a project's status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileStatus {
+    /// Path relative to the project directory
+    pub path: String,
+    pub state: FileState,
+    /// Whether the template renders the file differently than when it was
+    /// written, gained it or dropped it
+    pub template_changed: bool,
+}
+
+/// How the files of a scaffolded project have drifted from its template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProjectStatus {
+    files: Vec<FileStatus>,
+}
+
+impl ProjectStatus {
+    /// Compare the project in `target`, recorded in `lock`, with the `next`
+    /// rendering of its template
+    ///
+    /// Local changes take precedence: a file that was edited or deleted is
+    /// `modified` or `missing` whether or not the template changed too.
+    /// Files the template gained are `outdated` unless something rfe does not
+    /// manage is already in their place.
+    pub fn new(target: &Path, lock: &Lockfile, next: &[RenderedFile]) -> Result<Self> {
+        let mut files = Vec::new();
+        for locked in &lock.files {
+            let template_changed = match next.iter().find(|file| file.path == locked.path) {
+                Some(file) => content_hash(&file.contents) != locked.template_sha256,
+                None => true,
+            };
+            let state = match read_existing(&target.join(&locked.path))? {
+                None => FileState::Missing,
+                Some(current) if content_hash(&current) != locked.sha256 => FileState::Modified,
+                Some(_) if template_changed => FileState::Outdated,
+                Some(_) => FileState::Unmodified,
+            };
+            files.push(FileStatus {
+                path: locked.path.clone(),
+                state,
+                template_changed,
+            });
+        }
+        for file in next {
+            if lock.file(&file.path).is_none() && !target.join(&file.path).exists() {
+                files.push(FileStatus {
+                    path: file.path.clone(),
+                    state: FileState::Outdated,
+                    template_changed: true,
+                });
+            }
+        }
+        Ok(ProjectStatus { files })
+    }
+
+    pub fn files(&self) -> &[FileStatus] {
+        &self.files
+    }
+
+    /// Whether every managed file is unmodified
+    pub fn is_clean(&self) -> bool {
+        self.files
+            .iter()
+            .all(|file| file.state == FileState::Unmodified)
+    }
+}
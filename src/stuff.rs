@@ -1,53 +1,207 @@
+use crate::cache::Cache;
+use crate::error::{Result, RfeError};
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::plan::check_relative;
+use crate::source::{
+    EmbeddedSource, GitSource, GitUrl, LocalDirectorySource, SubdirSource, TemplateSource,
+};
 use git2::Repository;
-use include_dir::{include_dir, Dir};
-use std::fs::File;
-use std::io::{self, Read};
+use std::fmt;
 use std::path::{Path, PathBuf};
-use std::str::FromStr;
-use tempfile::TempDir;
 use url::Url;
 
-static PROJECT_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/init");
+/// Options controlling how a source path is resolved
+#[derive(Debug, Clone, Default)]
+pub struct SourceOptions {
+    /// Branch, tag or commit to read git sources at
+    pub git_ref: Option<String>,
+    /// Use cached remote repositories without fetching
+    pub offline: bool,
+    /// Cache for remote repositories; defaults to `Cache::from_env`
+    pub cache: Option<Cache>,
+    /// Hosts remote git sources may be fetched from; empty allows any host
+    pub allowed_hosts: Vec<String>,
+    /// Subdirectory of the source that holds the template
+    pub subdir: Option<String>,
+}
+
+/// Split the `source//subdir` shorthand into the source and the subdirectory
+///
+/// Only git URLs and scp-like remotes are split, at the first `//` after the
+/// repository path; local paths are returned whole, as `//` is a plain
+/// separator there.
+pub fn split_subdir(source: &str) -> (&str, Option<&str>) {
+    let start = source.find("://").map_or(0, |scheme| scheme + 3);
+    match source[start..].find("//") {
+        Some(index) if GitUrl::parse(&source[..start + index]).is_some() => {
+            let (path, subdir) = source.split_at(start + index);
+            (path, Some(&subdir[2..]))
+        }
+        _ => (source, None),
+    }
+}
 
-/// Represents different types of input sources
-#[derive(Debug, PartialEq)]
-enum SourceType {
-    LocalDirectory,
-    GitRepository,
-    Unknown,
+/// Where `SourceContentReader` found a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Provenance {
+    /// The file was read from the source
+    Source {
+        path: String,
+        /// Description of the source, as given by `describe`
+        source: String,
+        /// Commit the file was read at, for git sources
+        commit: Option<String>,
+    },
+    /// The file came from the scaffold embedded at build time
+    Embedded {
+        path: String,
+        /// Description of the source that lacks the file, if there is one
+        missing_from: Option<String>,
+    },
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Provenance::Source {
+                path,
+                source,
+                commit: Some(commit),
+            } => write!(f, "{} in {} at commit {}", path, source, commit),
+            Provenance::Source { path, source, .. } => write!(f, "{} in {}", path, source),
+            Provenance::Embedded {
+                path,
+                missing_from: Some(source),
+            } => write!(
+                f,
+                "{} from the embedded scaffold, {} does not have it",
+                path, source
+            ),
+            Provenance::Embedded { path, .. } => write!(f, "{} from the embedded scaffold", path),
+        }
+    }
 }
 
 /// Handles reading content from different source types
 pub struct SourceContentReader {
     path: String,
-    location: Option<PathBuf>,
-    source_type: SourceType,
-    temp_dir: Option<TempDir>,
+    subdir: Option<String>,
+    git_ref: Option<String>,
+    source: Option<Box<dyn TemplateSource>>,
+    fallback: EmbeddedSource,
 }
 
 impl SourceContentReader {
     /// Create a new SourceContentReader
-    pub fn new(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut scr = SourceContentReader {
-            path: path.to_string(),
-            location: None,
-            source_type: SourceType::Unknown,
-            temp_dir: None,
-        };
-        if scr.is_local_directory() {
-            scr.location = PathBuf::from_str(&scr.path).ok();
-            scr.source_type = SourceType::LocalDirectory;
+    pub fn new(path: &str) -> Result<Self> {
+        Self::open(path, &SourceOptions::default())
+    }
+
+    /// Create a new SourceContentReader, resolving `path` according to `options`
+    ///
+    /// `path` may use the `source//subdir` shorthand; it is combined with
+    /// `options.subdir` when both are given. The subdirectory must be relative
+    /// and free of `..`.
+    pub fn open(path: &str, options: &SourceOptions) -> Result<Self> {
+        let (path, shorthand) = split_subdir(path);
+        let parts: Vec<&str> = [shorthand, options.subdir.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.trim_matches('/').is_empty())
+            .collect();
+        for part in &parts {
+            check_relative(part)?;
         }
+        let subdir = parts
+            .iter()
+            .map(|part| part.trim_matches('/'))
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut scr = Self::embedded();
+        scr.path = path.to_string();
 
-        // Then check if it's a git repository URL
+        // Check if it's a git repository URL or a local git repository first
         if scr.is_git_repository() {
-            scr.setup_git_repository()?;
-            scr.source_type = SourceType::GitRepository;
+            scr.source = Some(scr.setup_git_repository(options)?);
+        } else if let Some(git_ref) = &options.git_ref {
+            if scr.is_local_directory() {
+                return Err(RfeError::RefUnsupported {
+                    reference: git_ref.clone(),
+                    source: scr.path,
+                });
+            }
+        } else if scr.is_local_directory() {
+            scr.source = Some(Box::new(LocalDirectorySource::new(&scr.path)));
         }
 
+        let source = match scr.source.take() {
+            Some(source) => source,
+            None => return Err(scr.classification_error()),
+        };
+        scr.source = Some(if subdir.is_empty() {
+            source
+        } else {
+            scr.root_at(source, &subdir)?
+        });
+        scr.subdir = Some(subdir).filter(|subdir| !subdir.is_empty());
+        scr.git_ref = options.git_ref.clone();
+
         Ok(scr)
     }
 
+    /// Root lookups at `subdir`, which must contain at least one file
+    fn root_at(
+        &self,
+        source: Box<dyn TemplateSource>,
+        subdir: &str,
+    ) -> Result<Box<dyn TemplateSource>> {
+        let source = SubdirSource::new(source, subdir)?;
+        if source.list_files()?.is_empty() {
+            return Err(RfeError::SubdirNotFound {
+                subdir: subdir.to_string(),
+                source: self.path.clone(),
+            });
+        }
+        Ok(Box::new(source))
+    }
+
+    /// Create a SourceContentReader backed only by the embedded scaffold
+    pub fn embedded() -> Self {
+        SourceContentReader {
+            path: String::new(),
+            subdir: None,
+            git_ref: None,
+            source: None,
+            fallback: EmbeddedSource,
+        }
+    }
+
+    /// Create a SourceContentReader over a custom `TemplateSource` backend
+    pub fn with_source(source: Box<dyn TemplateSource>) -> Self {
+        SourceContentReader {
+            path: source.describe(),
+            subdir: None,
+            git_ref: None,
+            source: Some(source),
+            fallback: EmbeddedSource,
+        }
+    }
+
+    /// Explain why the path could not be classified
+    fn classification_error(&self) -> RfeError {
+        let path = Path::new(&self.path);
+        if Url::parse(&self.path).is_err() && !path.exists() {
+            RfeError::SourceNotFound {
+                path: path.to_path_buf(),
+            }
+        } else {
+            RfeError::InvalidSource {
+                source: self.path.clone(),
+            }
+        }
+    }
+
     /// Check if the path is a local directory
     fn is_local_directory(&self) -> bool {
         let path = Path::new(&self.path);
@@ -56,76 +210,169 @@ impl SourceContentReader {
 
     /// Check if the path is a git repository URL or local git repository
     fn is_git_repository(&self) -> bool {
-        // Check if it's a remote git URL
-        if let Ok(url) = Url::parse(&self.path) {
-            return self.validate_git_url(&url);
+        // Check if it's a local git repository, with a working tree or bare
+        let path = Path::new(&self.path);
+        if path.exists() {
+            return path.join(".git").exists() || self.is_bare_repository();
         }
 
-        // Check if it's a local git repository
-        let path = Path::new(&self.path);
-        path.exists() && path.join(".git").exists()
+        // Check if it's a remote git URL
+        GitUrl::parse(&self.path).is_some()
     }
 
-    /// Validate git repository URL
-    fn validate_git_url(&self, url: &Url) -> bool {
-        let git_hosts = ["github.com", "gitlab.com", "bitbucket.org"];
+    fn is_bare_repository(&self) -> bool {
+        Repository::open_bare(&self.path).is_ok()
+    }
 
-        url.scheme() == "https"
-            && git_hosts.contains(&url.host_str().unwrap_or(""))
-            && (url.path().ends_with(".git") || url.path().contains("/"))
+    /// Validate the git URL's host against the configured allowlist
+    fn validate_git_url(&self, options: &SourceOptions) -> Result<()> {
+        if options.allowed_hosts.is_empty() {
+            return Ok(());
+        }
+        let host = match GitUrl::parse(&self.path).and_then(|url| url.host) {
+            Some(host) => host,
+            // Local paths and file:// URLs have no host to check
+            None => return Ok(()),
+        };
+        if options
+            .allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(&host))
+        {
+            Ok(())
+        } else {
+            Err(RfeError::HostNotAllowed { host })
+        }
     }
 
-    /// Read contents of a specific file based on source type
-    pub fn read_file_contents(&self, filename: &str) -> Result<String, Box<dyn std::error::Error>> {
-        match self.source_type {
-            SourceType::LocalDirectory | SourceType::GitRepository => {
-                // Read from local directory
-                Ok(self.read_local_file(filename)?)
-            }
-            SourceType::Unknown => Ok(self.read_fallback(filename)?),
+    /// Describe where files are read from, ignoring the embedded fallback
+    pub fn describe(&self) -> String {
+        match &self.source {
+            Some(source) => source.describe(),
+            None => self.fallback.describe(),
         }
     }
 
-    fn read_fallback(&self, filename: &str) -> Result<String, io::Error> {
-        let file_path = PROJECT_DIR.get_file(filename).unwrap();
-        let body = file_path.contents_utf8().unwrap();
-        return Ok(body.to_string());
+    /// The source path or URL files are read from, without any `//subdir`
+    ///
+    /// `None` when only the embedded scaffold is used.
+    pub fn location(&self) -> Option<&str> {
+        self.source.as_ref().map(|_| self.path.as_str())
     }
 
-    /// Read file from local directory
-    fn read_local_file(&self, filename: &str) -> Result<String, io::Error> {
-        let file_path = self.location.as_ref().unwrap().join(filename);
+    /// Subdirectory of the source the template is read from
+    pub fn subdir(&self) -> Option<&str> {
+        self.subdir.as_deref()
+    }
 
-        if !file_path.exists() {
-            return self.read_fallback(filename);
-        }
+    /// Branch, tag or commit the source was asked to be read at
+    pub fn git_ref(&self) -> Option<&str> {
+        self.git_ref.as_deref()
+    }
 
-        // Open the file
-        let mut file = File::open(file_path)?;
+    /// Commit id the source was resolved to, for git sources
+    pub fn revision(&self) -> Option<String> {
+        self.source.as_ref().and_then(|source| source.revision())
+    }
 
-        // Read contents to string
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+    /// List every file the source offers
+    ///
+    /// Only the embedded scaffold's files are listed when there is no other
+    /// source; they are not merged into another source's list.
+    pub fn list_files(&self) -> Result<Vec<String>> {
+        match &self.source {
+            Some(source) => source.list_files(),
+            None => self.fallback.list_files(),
+        }
+    }
 
-        Ok(contents)
+    /// Parse the template's `rfe.toml`, if the source has one
+    ///
+    /// The embedded scaffold never has a manifest.
+    pub fn manifest(&self) -> Result<Option<Manifest>> {
+        let source = match &self.source {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+        match source.read_bytes(MANIFEST_FILE)? {
+            Some(bytes) => {
+                let contents = Self::decode(MANIFEST_FILE, source.as_ref(), bytes)?;
+                Manifest::parse(&contents, &source.describe()).map(Some)
+            }
+            None => Ok(None),
+        }
     }
 
-    fn setup_git_repository(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let temp_dir = tempfile::tempdir()?;
-        // Temporary directory for cloning if it's a remote repository
-        let repo_path = if self.is_local_directory() {
-            // If it's a local git repository, use the existing path
-            PathBuf::from(&self.path)
-        } else {
-            // Clone remote repository to a temporary directory
-            let repo_path = temp_dir.path().to_path_buf();
+    /// Read contents of a specific file, falling back to the embedded scaffold
+    pub fn read_file_contents(&self, filename: &str) -> Result<String> {
+        self.read_file(filename).map(|(contents, _)| contents)
+    }
 
-            // Clone the repository
-            Repository::clone(&self.path, &repo_path)?;
-            repo_path
+    /// Read a file like `read_file_contents`, reporting where it was found
+    pub fn read_file(&self, filename: &str) -> Result<(String, Provenance)> {
+        if let Some(source) = &self.source {
+            if let Some(bytes) = source.read_bytes(filename)? {
+                let provenance = Provenance::Source {
+                    path: filename.to_string(),
+                    source: source.describe(),
+                    commit: source.revision(),
+                };
+                return Ok((Self::decode(filename, source.as_ref(), bytes)?, provenance));
+            }
+        }
+        let provenance = Provenance::Embedded {
+            path: filename.to_string(),
+            missing_from: self.source.as_ref().map(|source| source.describe()),
         };
-        self.location = Some(repo_path);
-        self.temp_dir = Some(temp_dir);
-        Ok(())
+        Ok((self.read_fallback(filename)?, provenance))
+    }
+
+    /// Read file from the scaffold embedded at build time
+    fn read_fallback(&self, filename: &str) -> Result<String> {
+        let bytes =
+            self.fallback
+                .read_bytes(filename)?
+                .ok_or_else(|| RfeError::FileMissingEverywhere {
+                    filename: filename.to_string(),
+                    source: self.path.clone(),
+                })?;
+        Self::decode(filename, &self.fallback, bytes)
+    }
+
+    fn decode(filename: &str, source: &dyn TemplateSource, bytes: Vec<u8>) -> Result<String> {
+        String::from_utf8(bytes).map_err(|_| RfeError::NotUtf8 {
+            path: PathBuf::from(filename),
+            source: source.describe(),
+        })
+    }
+
+    fn setup_git_repository(&self, options: &SourceOptions) -> Result<Box<dyn TemplateSource>> {
+        let git_ref = options.git_ref.as_deref();
+        if !self.is_local_directory() {
+            self.validate_git_url(options)?;
+
+            // Fetch the remote repository into the cache, or a temporary
+            // bare repository when there is nowhere to cache it
+            let source = match options.cache.clone().or_else(Cache::from_env) {
+                Some(cache) => GitSource::cached(&cache, &self.path, git_ref, options.offline)?,
+                None if options.offline => {
+                    return Err(RfeError::NotCached {
+                        url: self.path.clone(),
+                    })
+                }
+                None => GitSource::remote(&self.path, git_ref)?,
+            };
+            return Ok(Box::new(source));
+        }
+        match git_ref {
+            // Read the local repository at a commit, ignoring uncommitted edits
+            Some(git_ref) => Ok(Box::new(GitSource::local(Path::new(&self.path), git_ref)?)),
+            // Bare repositories have no working tree, read their HEAD commit
+            None if self.is_bare_repository() => {
+                Ok(Box::new(GitSource::local(Path::new(&self.path), "HEAD")?))
+            }
+            // Otherwise use the existing working tree
+            None => Ok(Box::new(GitSource::open(&self.path))),
+        }
     }
 }
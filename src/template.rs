@@ -0,0 +1,166 @@
+use crate::error::{Result, RfeError};
+use crate::manifest::Manifest;
+use heck::{ToKebabCase, ToLowerCamelCase, ToSnakeCase, ToUpperCamelCase};
+use minijinja::{Environment, UndefinedBehavior};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Prefix of environment variables that provide template variables
+///
+/// `RFE_VAR_PROJECT_NAME=demo` sets `project_name`.
+pub const ENV_PREFIX: &str = "RFE_VAR_";
+
+/// Values substituted into template files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variables {
+    values: BTreeMap<String, String>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(name.into(), value.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Parse a `name=value` assignment as given to `--var`
+    pub fn parse_assignment(assignment: &str) -> Result<(String, String)> {
+        match assignment.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                Ok((name.trim().to_string(), value.to_string()))
+            }
+            _ => Err(RfeError::InvalidVariable {
+                assignment: assignment.to_string(),
+            }),
+        }
+    }
+
+    /// Collect `RFE_VAR_*` variables from the process environment
+    pub fn from_env() -> Self {
+        let mut variables = Self::new();
+        for (key, value) in env::vars() {
+            if let Some(name) = key.strip_prefix(ENV_PREFIX) {
+                variables.set(name.to_lowercase(), value);
+            }
+        }
+        variables
+    }
+
+    /// Add every value from `other`, replacing existing ones
+    pub fn extend(&mut self, other: Variables) {
+        self.values.extend(other.values);
+    }
+
+    /// Only the values of variables the manifest declares
+    pub fn declared_in(&self, manifest: &Manifest) -> Variables {
+        let values = manifest
+            .variables
+            .iter()
+            .filter_map(|v| Some((v.name.clone(), self.values.get(&v.name)?.clone())))
+            .collect();
+        Variables { values }
+    }
+
+    /// Load answers from a TOML file of `name = value` pairs
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| RfeError::io(path, e))?;
+        let invalid = |message: String| RfeError::InvalidAnswersFile {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let table: toml::Table =
+            toml::from_str(&contents).map_err(|e| invalid(e.message().into()))?;
+        let mut variables = Self::new();
+        for (name, value) in table {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Boolean(b) => b.to_string(),
+                toml::Value::Integer(i) => i.to_string(),
+                toml::Value::Float(f) => f.to_string(),
+                other => return Err(invalid(format!("{} has unsupported value {}", name, other))),
+            };
+            variables.set(name, value);
+        }
+        Ok(variables)
+    }
+
+    /// Layer manifest defaults, `provided` values (answers, the environment)
+    /// and explicit assignments, in increasing order of precedence
+    ///
+    /// Values are validated against the manifest, and it fails when a
+    /// variable the manifest marks as required has no value.
+    pub fn resolve(
+        manifest: Option<&Manifest>,
+        provided: Variables,
+        assignments: &[(String, String)],
+    ) -> Result<Self> {
+        let mut variables = Self::new();
+        for variable in manifest.iter().flat_map(|m| &m.variables) {
+            if let Some(default) = &variable.default {
+                variables.set(&variable.name, default);
+            }
+        }
+        variables.extend(provided);
+        for (name, value) in assignments {
+            variables.set(name, value);
+        }
+
+        let mut missing = Vec::new();
+        for variable in manifest.iter().flat_map(|m| &m.variables) {
+            match variables.values.get_mut(&variable.name) {
+                Some(value) => *value = variable.validate(value)?,
+                None if variable.required => missing.push(variable.name.clone()),
+                None => {}
+            }
+        }
+        if !missing.is_empty() {
+            return Err(RfeError::MissingVariables { names: missing });
+        }
+        Ok(variables)
+    }
+}
+
+/// Render `contents` of the template file `name` with `variables`
+///
+/// Uses Jinja syntax (`{{ name }}`, `{% if %}`, `{% for %}`) with the extra
+/// filters `snake_case`, `kebab_case`, `camel_case` and `pascal_case`.
+/// Referring to a variable without a value is an error.
+pub fn render(name: &str, contents: &str, variables: &Variables) -> Result<String> {
+    let template_error = |e: minijinja::Error| RfeError::Template {
+        file: name.to_string(),
+        message: match e.line() {
+            Some(line) => format!("line {}: {}", line, e),
+            None => e.to_string(),
+        },
+    };
+
+    let mut environment = Environment::new();
+    environment.set_undefined_behavior(UndefinedBehavior::Strict);
+    environment.set_keep_trailing_newline(true);
+    environment.add_filter("snake_case", |s: String| s.to_snake_case());
+    environment.add_filter("kebab_case", |s: String| s.to_kebab_case());
+    environment.add_filter("camel_case", |s: String| s.to_lower_camel_case());
+    environment.add_filter("pascal_case", |s: String| s.to_upper_camel_case());
+
+    let template = environment
+        .template_from_named_str(name, contents)
+        .map_err(template_error)?;
+    template.render(&variables.values).map_err(template_error)
+}
@@ -0,0 +1,226 @@
+use crate::error::{Result, RfeError};
+use crate::lock::{content_hash, Lockfile, LOCK_FILE};
+use crate::merge::{strip_managed_block, ManagedFile};
+use crate::plan::{backup_path, check_relative, read_existing, FileAction};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What `uninstall` does with one managed file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UninstallAction {
+    /// rfe wrote the whole file; it is deleted
+    Remove,
+    /// rfe replaced the file after keeping `<file>.orig`; the backup is
+    /// moved back
+    Restore,
+    /// rfe merged a managed block into the file; the block is removed
+    Strip,
+    /// The file is left as it is
+    Keep,
+}
+
+impl fmt::Display for UninstallAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UninstallAction::Remove => "remove",
+            UninstallAction::Restore => "restore",
+            UninstallAction::Strip => "strip",
+            UninstallAction::Keep => "keep",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How one managed file is undone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UninstalledFile {
+    /// Path relative to the project directory
+    pub path: String,
+    pub action: UninstallAction,
+    /// Contents currently in the project, if the file exists
+    pub existing: Option<String>,
+    /// Whether the file changed since rfe wrote it
+    pub modified: bool,
+    /// Whether removing the file may lose something rfe cannot give back:
+    /// a file it replaced without a backup, or one the lockfile cannot
+    /// vouch rfe created
+    pub unrecoverable: bool,
+    /// Why the file is not fully restored, if it is not
+    pub note: Option<String>,
+}
+
+impl UninstalledFile {
+    /// Whether undoing the file throws away changes made since rfe wrote it
+    pub fn discards_changes(&self) -> bool {
+        self.modified
+            && matches!(
+                self.action,
+                UninstallAction::Remove | UninstallAction::Restore
+            )
+    }
+
+    /// Whether undoing the file needs `force`
+    pub fn needs_force(&self) -> bool {
+        self.discards_changes() || self.unrecoverable
+    }
+}
+
+/// The steps that undo the scaffold of a project
+#[derive(Debug, Clone)]
+pub struct UninstallPlan {
+    target: PathBuf,
+    files: Vec<UninstalledFile>,
+}
+
+impl UninstallPlan {
+    /// Plan undoing the scaffold of the project in `target`, recorded in `lock`
+    ///
+    /// Files rfe created or overwrote are removed, files it backed up get
+    /// their `.orig` copy back and files it merged into lose their
+    /// rfe-managed block. Merged files without one are left in place, as
+    /// rfe's lines cannot be told apart from the user's.
+    pub fn new(target: impl Into<PathBuf>, lock: &Lockfile) -> Result<Self> {
+        let target = target.into();
+        let mut files = Vec::new();
+        for locked in &lock.files {
+            let file_path = target.join(&locked.path);
+            let existing = read_existing(&file_path)?;
+            let modified = existing
+                .as_ref()
+                .is_some_and(|existing| content_hash(existing) != locked.sha256);
+            let has_backup = backup_path(&file_path).is_file();
+
+            // Version 1 lockfiles also recorded files the user already had
+            // as created, and a created file only differs from its template
+            // when local changes were merged into it
+            let trusted = lock.version >= 2 && locked.sha256 == locked.template_sha256;
+
+            let (action, note, unrecoverable) = match (locked.action, &existing) {
+                (FileAction::Backup, _) if has_backup => (UninstallAction::Restore, None, false),
+                (_, None) => (UninstallAction::Keep, Some("already deleted"), false),
+                (FileAction::Create, Some(_)) if trusted => (UninstallAction::Remove, None, false),
+                (FileAction::Create, Some(_)) => (
+                    UninstallAction::Remove,
+                    Some("the lockfile cannot tell whether rfe created the file"),
+                    true,
+                ),
+                (FileAction::Overwrite, Some(_)) => (
+                    UninstallAction::Remove,
+                    Some("the file rfe overwrote was not backed up"),
+                    true,
+                ),
+                (FileAction::Backup, Some(_)) => (
+                    UninstallAction::Remove,
+                    Some("its .orig backup is gone"),
+                    true,
+                ),
+                (FileAction::Merge, Some(existing))
+                    if ManagedFile::parse(existing).block_lines().is_some() =>
+                {
+                    (UninstallAction::Strip, None, false)
+                }
+                (FileAction::Merge, Some(_)) => (
+                    UninstallAction::Keep,
+                    Some("rfe's changes are merged into the file; remove them by hand"),
+                    false,
+                ),
+                (FileAction::Skip, Some(_)) => (UninstallAction::Keep, None, false),
+            };
+            files.push(UninstalledFile {
+                path: locked.path.clone(),
+                action,
+                existing,
+                modified,
+                unrecoverable,
+                note: note.map(String::from),
+            });
+        }
+        Ok(UninstallPlan { target, files })
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    pub fn files(&self) -> &[UninstalledFile] {
+        &self.files
+    }
+
+    /// Undo every file, then remove `.rfe.lock`
+    ///
+    /// Nothing is touched when a file would lose changes made since rfe
+    /// wrote it, or contents rfe cannot restore, unless `force` is set.
+    /// Directories left empty are removed. Returns the paths that were
+    /// removed or rewritten, in plan order.
+    pub fn apply(&self, force: bool) -> Result<Vec<PathBuf>> {
+        for file in &self.files {
+            check_relative(&file.path)?;
+        }
+        let modified: Vec<String> = self
+            .files
+            .iter()
+            .filter(|file| file.discards_changes())
+            .map(|file| file.path.clone())
+            .collect();
+        if !force && !modified.is_empty() {
+            return Err(RfeError::LocallyModified { paths: modified });
+        }
+        let unrecoverable: Vec<String> = self
+            .files
+            .iter()
+            .filter(|file| file.unrecoverable)
+            .map(|file| file.path.clone())
+            .collect();
+        if !force && !unrecoverable.is_empty() {
+            return Err(RfeError::Unrecoverable {
+                paths: unrecoverable,
+            });
+        }
+
+        let mut touched = Vec::new();
+        for file in &self.files {
+            let file_path = self.target.join(&file.path);
+            match (file.action, &file.existing) {
+                (UninstallAction::Remove, _) => {
+                    remove_file(&file_path)?;
+                    self.remove_empty_parents(&file_path);
+                }
+                (UninstallAction::Restore, _) => {
+                    let backup_path = backup_path(&file_path);
+                    fs::rename(&backup_path, &file_path)
+                        .map_err(|e| RfeError::io(&backup_path, e))?;
+                }
+                (UninstallAction::Strip, Some(existing)) => {
+                    fs::write(&file_path, strip_managed_block(existing))
+                        .map_err(|e| RfeError::io(&file_path, e))?;
+                }
+                _ => continue,
+            }
+            touched.push(file_path);
+        }
+        let lock_path = self.target.join(LOCK_FILE);
+        if lock_path.is_file() {
+            remove_file(&lock_path)?;
+            touched.push(lock_path);
+        }
+        Ok(touched)
+    }
+
+    /// Remove the directories between `path` and the target that are empty
+    fn remove_empty_parents(&self, path: &Path) {
+        for dir in path.ancestors().skip(1) {
+            if dir == self.target || fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(RfeError::io(path, e)),
+        _ => Ok(()),
+    }
+}
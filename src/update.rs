@@ -0,0 +1,393 @@
+use crate::error::{Result, RfeError};
+use crate::lock::{content_hash, LockedFile, LockedSource, Lockfile};
+use crate::manifest::Manifest;
+use crate::merge::{self, merge_lines};
+use crate::plan::{check_relative, read_existing, unified_diff, FileAction};
+use crate::scaffold::RenderedFile;
+use crate::template::Variables;
+use diffy::{ConflictStyle, MergeOptions};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Suffix of the file holding template changes `update` could not apply
+pub const REJECT_SUFFIX: &str = ".rej";
+
+/// What `update` does with one managed file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateOutcome {
+    /// The template did not change the file, or it already matches
+    Unchanged,
+    /// The file was not modified locally and takes the new template as is
+    Updated,
+    /// Local and template changes were merged cleanly
+    Merged,
+    /// Both sides changed the same lines; conflict markers are written
+    Conflicted,
+    /// Both sides changed the same lines; the template changes go to a
+    /// `.rej` file and the local file is left alone
+    Rejected,
+    /// The template gained the file
+    Created,
+    /// The file was deleted locally and stays deleted
+    DeletedLocally,
+    /// The file exists but rfe does not manage it
+    Unmanaged,
+    /// The template no longer has the file; it is left in place
+    RemovedUpstream,
+}
+
+impl fmt::Display for UpdateOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UpdateOutcome::Unchanged => "unchanged",
+            UpdateOutcome::Updated => "updated",
+            UpdateOutcome::Merged => "merged",
+            UpdateOutcome::Conflicted => "conflict",
+            UpdateOutcome::Rejected => "rejected",
+            UpdateOutcome::Created => "created",
+            UpdateOutcome::DeletedLocally => "deleted locally",
+            UpdateOutcome::Unmanaged => "not managed",
+            UpdateOutcome::RemovedUpstream => "removed from template",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How one managed file is brought up to date
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileUpdate {
+    /// Path relative to the project directory
+    pub path: String,
+    pub outcome: UpdateOutcome,
+    /// Contents currently in the project, if the file exists
+    pub existing: Option<String>,
+    /// Contents to write, when the file changes
+    pub contents: Option<String>,
+    /// Patch of template changes written to `<file>.rej`
+    pub reject: Option<String>,
+    /// The file as the new template renders it
+    pub template: Option<String>,
+    /// What a structured merge could not reconcile
+    pub notes: Vec<String>,
+    locked: Option<LockedFile>,
+}
+
+impl FileUpdate {
+    /// Unified diff from the current contents to the updated ones
+    ///
+    /// Empty when the file does not change.
+    pub fn diff(&self) -> String {
+        let contents = match &self.contents {
+            Some(contents) if self.existing.as_ref() != Some(contents) => contents,
+            _ => return String::new(),
+        };
+        unified_diff(&self.path, self.existing.as_deref(), contents)
+    }
+
+    /// The lockfile entry for the file after the update, `None` when rfe no
+    /// longer manages it
+    fn locked(&self) -> Option<LockedFile> {
+        let template = match &self.template {
+            Some(template) => template,
+            None => return None,
+        };
+        let previous = self.locked.clone();
+        match self.outcome {
+            UpdateOutcome::Unmanaged | UpdateOutcome::RemovedUpstream => None,
+            // A file that already matches a changed template now has what
+            // the template renders
+            UpdateOutcome::Unchanged => previous.map(|locked| match &self.existing {
+                Some(current) if content_hash(template) != locked.template_sha256 => LockedFile {
+                    sha256: content_hash(current),
+                    template_sha256: content_hash(template),
+                    ..locked
+                },
+                _ => locked,
+            }),
+            // Unresolved files keep the old template's hash; with the old
+            // commit kept by `UpdatePlan::lockfile`, the next update merges
+            // the template change again
+            UpdateOutcome::DeletedLocally | UpdateOutcome::Rejected => previous,
+            UpdateOutcome::Conflicted => previous.map(|locked| LockedFile {
+                sha256: self
+                    .contents
+                    .as_deref()
+                    .map_or(locked.sha256.clone(), content_hash),
+                ..locked
+            }),
+            UpdateOutcome::Updated | UpdateOutcome::Merged | UpdateOutcome::Created => {
+                Some(LockedFile {
+                    path: self.path.clone(),
+                    action: previous.map_or(FileAction::Create, |locked| locked.action),
+                    sha256: content_hash(self.contents.as_deref().unwrap_or_default()),
+                    template_sha256: content_hash(template),
+                })
+            }
+        }
+    }
+}
+
+/// The changes that bring a scaffolded project up to date with its template
+#[derive(Debug, Clone)]
+pub struct UpdatePlan {
+    target: PathBuf,
+    files: Vec<FileUpdate>,
+    /// Format version of the lockfile the plan was made from
+    lock_version: u32,
+    /// Commit the lockfile the plan was made from records
+    base_commit: Option<String>,
+}
+
+impl UpdatePlan {
+    /// Plan updating the project in `target`, recorded in `lock`, to the
+    /// `next` rendering of its template
+    ///
+    /// `base` is the template as rendered when the project was last written,
+    /// if it can still be read; it is the common ancestor of three-way
+    /// merges. Files rfe merged into on `init` are merged again instead.
+    /// When `reject` is set, conflicting changes go to `.rej` files rather
+    /// than conflict markers.
+    pub fn new(
+        target: impl Into<PathBuf>,
+        lock: &Lockfile,
+        base: Option<&[RenderedFile]>,
+        next: Vec<RenderedFile>,
+        reject: bool,
+    ) -> Result<Self> {
+        let target = target.into();
+        let mut files = Vec::new();
+        for file in next {
+            let existing = read_existing(&target.join(&file.path))?;
+            let base = base
+                .and_then(|base| base.iter().find(|b| b.path == file.path))
+                .map(|b| b.contents.as_str());
+            let locked = lock.file(&file.path).cloned();
+            let rejected = reject_path(&target.join(&file.path)).is_file();
+            files.push(plan_file(file, existing, locked, base, reject, rejected));
+        }
+        for locked in &lock.files {
+            if !files.iter().any(|file| file.path == locked.path) {
+                files.push(FileUpdate {
+                    path: locked.path.clone(),
+                    outcome: UpdateOutcome::RemovedUpstream,
+                    existing: None,
+                    contents: None,
+                    reject: None,
+                    template: None,
+                    notes: Vec::new(),
+                    locked: Some(locked.clone()),
+                });
+            }
+        }
+        Ok(UpdatePlan {
+            target,
+            files,
+            lock_version: lock.version,
+            base_commit: lock.source.commit.clone(),
+        })
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    pub fn files(&self) -> &[FileUpdate] {
+        &self.files
+    }
+
+    /// Write every changed file and `.rej` file
+    ///
+    /// Returns the paths that were written, in plan order.
+    pub fn apply(&self) -> Result<Vec<PathBuf>> {
+        for file in &self.files {
+            check_relative(&file.path)?;
+        }
+        let mut written = Vec::new();
+        for file in &self.files {
+            let file_path = self.target.join(&file.path);
+            if let Some(reject) = &file.reject {
+                let reject_path = reject_path(&file_path);
+                fs::write(&reject_path, reject).map_err(|e| RfeError::io(&reject_path, e))?;
+                written.push(reject_path);
+            }
+            let contents = match &file.contents {
+                Some(contents) if file.existing.as_ref() != Some(contents) => contents,
+                _ => continue,
+            };
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent).map_err(|e| RfeError::io(parent, e))?;
+            }
+            fs::write(&file_path, contents).map_err(|e| RfeError::io(&file_path, e))?;
+            written.push(file_path);
+        }
+        Ok(written)
+    }
+
+    /// Files left with conflict markers or a `.rej` file
+    pub fn unresolved(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|file| {
+                matches!(
+                    file.outcome,
+                    UpdateOutcome::Conflicted | UpdateOutcome::Rejected
+                )
+            })
+            .map(|file| file.path.clone())
+            .collect()
+    }
+
+    /// The lockfile describing the project after the update
+    ///
+    /// While files are unresolved the previous commit is kept in place of
+    /// `source`'s, so the next update still merges from the old template.
+    pub fn lockfile(
+        &self,
+        source: LockedSource,
+        manifest: Option<&Manifest>,
+        variables: &Variables,
+    ) -> Lockfile {
+        let files = self.files.iter().filter_map(FileUpdate::locked).collect();
+        let mut lock = Lockfile::new(source, manifest, variables, files);
+        lock.version = lock.version.min(self.lock_version);
+        if !self.unresolved().is_empty() {
+            lock.source.commit = self.base_commit.clone();
+        }
+        lock
+    }
+}
+
+/// Decide how to bring one file rendered by the new template up to date
+fn plan_file(
+    file: RenderedFile,
+    existing: Option<String>,
+    locked: Option<LockedFile>,
+    base: Option<&str>,
+    reject: bool,
+    rejected: bool,
+) -> FileUpdate {
+    let mut update = FileUpdate {
+        path: file.path,
+        outcome: UpdateOutcome::Unchanged,
+        existing,
+        contents: None,
+        reject: None,
+        template: Some(file.contents),
+        notes: Vec::new(),
+        locked,
+    };
+    let template = update.template.as_deref().unwrap_or_default();
+    let (current, locked) = match (&update.existing, &update.locked) {
+        (Some(current), Some(locked)) => (current, locked),
+        (None, Some(_)) => {
+            update.outcome = UpdateOutcome::DeletedLocally;
+            return update;
+        }
+        (None, None) => {
+            update.outcome = UpdateOutcome::Created;
+            update.contents = Some(template.to_string());
+            return update;
+        }
+        (Some(_), None) => {
+            update.outcome = UpdateOutcome::Unmanaged;
+            return update;
+        }
+    };
+
+    // A conflict from an earlier update stays one until it is resolved
+    if has_conflict_markers(current) {
+        update.outcome = UpdateOutcome::Conflicted;
+        update.notes.push("unresolved conflict markers".to_string());
+        return update;
+    }
+    if rejected {
+        update.outcome = UpdateOutcome::Rejected;
+        update
+            .notes
+            .push(format!("unresolved {} file", REJECT_SUFFIX));
+        return update;
+    }
+
+    let (outcome, contents, rejected, notes) =
+        if content_hash(template) == locked.template_sha256 || current == template {
+            (UpdateOutcome::Unchanged, None, None, Vec::new())
+        } else if locked.action == FileAction::Merge {
+            // Files merged on init are merged again, which updates rfe's part
+            let merged = merge::merge(&update.path, current, template);
+            (
+                UpdateOutcome::Merged,
+                Some(merged.contents),
+                None,
+                merged.conflicts,
+            )
+        } else if content_hash(current) == locked.sha256 {
+            (
+                UpdateOutcome::Updated,
+                Some(template.to_string()),
+                None,
+                Vec::new(),
+            )
+        } else {
+            let ancestor = base.unwrap_or(current);
+            let merged = match base {
+                Some(base) => MergeOptions::new()
+                    .set_conflict_style(ConflictStyle::Merge)
+                    .merge(base, current, template)
+                    .map_err(|conflicted| relabel_markers(&conflicted)),
+                // Without the old template every differing region conflicts
+                None => {
+                    let merged = merge_lines(current, template);
+                    match merged.conflicts.is_empty() {
+                        true => Ok(merged.contents),
+                        false => Err(merged.contents),
+                    }
+                }
+            };
+            match merged {
+                Ok(merged) => (UpdateOutcome::Merged, Some(merged), None, Vec::new()),
+                Err(_) if reject => (
+                    UpdateOutcome::Rejected,
+                    None,
+                    Some(diffy::create_patch(ancestor, template).to_string()),
+                    Vec::new(),
+                ),
+                Err(conflicted) => (
+                    UpdateOutcome::Conflicted,
+                    Some(conflicted),
+                    None,
+                    Vec::new(),
+                ),
+            }
+        };
+    update.outcome = outcome;
+    update.contents = contents;
+    update.reject = rejected;
+    update.notes = notes;
+    update
+}
+
+/// Whether `contents` still has the conflict markers `update` writes
+fn has_conflict_markers(contents: &str) -> bool {
+    let mut lines = contents.lines();
+    lines.any(|line| line == "<<<<<<< existing") && lines.any(|line| line == ">>>>>>> template")
+}
+
+/// Label conflict markers like `merge_lines` does
+fn relabel_markers(conflicted: &str) -> String {
+    conflicted
+        .split_inclusive('\n')
+        .map(|line| match line.trim_end() {
+            "<<<<<<< ours" => "<<<<<<< existing\n",
+            ">>>>>>> theirs" => ">>>>>>> template\n",
+            _ => line,
+        })
+        .collect()
+}
+
+/// Path of the `.rej` file `update` writes next to `path`
+pub fn reject_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(REJECT_SUFFIX);
+    PathBuf::from(name)
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::commit;
+use git2::Repository;
+use repo_file_expander::cache::Cache;
+use repo_file_expander::source::GitSource;
+use repo_file_expander::{RfeError, TemplateSource};
+use std::fs;
+use std::time::Duration;
+
+#[test]
+fn keys_ignore_scheme_and_git_suffix() {
+    assert_eq!(
+        Cache::key("https://GitHub.com/org/templates.git"),
+        Cache::key("ssh://git@github.com/org/templates/")
+    );
+    assert_ne!(
+        Cache::key("https://github.com/org/templates"),
+        Cache::key("https://github.com/org/other")
+    );
+}
+
+#[test]
+fn offline_uses_cached_copy_and_fetch_updates_it() {
+    let upstream = tempfile::tempdir().unwrap();
+    let repo = Repository::init(upstream.path()).unwrap();
+    let first = commit(&repo, "{ version = 1; }\n");
+    let url = upstream.path().to_str().unwrap();
+
+    let cache_dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(cache_dir.path());
+
+    let err = GitSource::cached(&cache, url, None, true).err().unwrap();
+    assert!(matches!(err, RfeError::NotCached { .. }));
+
+    let source = GitSource::cached(&cache, url, None, false).unwrap();
+    assert_eq!(source.revision(), Some(first.to_string()));
+
+    let second = commit(&repo, "{ version = 2; }\n");
+    let offline = GitSource::cached(&cache, url, None, true).unwrap();
+    assert_eq!(offline.revision(), Some(first.to_string()));
+
+    let online = GitSource::cached(&cache, url, None, false).unwrap();
+    assert_eq!(online.revision(), Some(second.to_string()));
+    assert_eq!(
+        online.read_bytes("devenv.nix").unwrap().unwrap(),
+        b"{ version = 2; }\n"
+    );
+}
+
+#[test]
+fn list_prune_and_clear() {
+    let upstream = tempfile::tempdir().unwrap();
+    let repo = Repository::init(upstream.path()).unwrap();
+    commit(&repo, "{ }\n");
+    let url = upstream.path().to_str().unwrap();
+
+    let cache_dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(cache_dir.path());
+    GitSource::cached(&cache, url, None, false).unwrap();
+
+    let entries = cache.entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].url.as_deref(), Some(url));
+    assert!(entries[0].last_used.is_some());
+
+    assert!(cache.prune(Duration::from_secs(3600)).unwrap().is_empty());
+    assert_eq!(cache.clear().unwrap(), 1);
+    assert!(cache.entries().unwrap().is_empty());
+}
+
+#[test]
+fn ignores_directories_that_are_not_cached_repositories() {
+    let upstream = tempfile::tempdir().unwrap();
+    let repo = Repository::init(upstream.path()).unwrap();
+    commit(&repo, "{ }\n");
+    let url = upstream.path().to_str().unwrap();
+
+    let cache_dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(cache_dir.path());
+    GitSource::cached(&cache, url, None, false).unwrap();
+
+    // A user directory, a key-named directory that is not a repository and a
+    // bare repository that has never been touched
+    fs::create_dir(cache_dir.path().join("my notes")).unwrap();
+    fs::write(cache_dir.path().join("my notes/todo.txt"), "keep\n").unwrap();
+    fs::create_dir(cache_dir.path().join("not-a-repo")).unwrap();
+    Repository::init_bare(cache_dir.path().join("untouched")).unwrap();
+
+    let keys: Vec<_> = cache
+        .entries()
+        .unwrap()
+        .into_iter()
+        .map(|e| e.key)
+        .collect();
+    assert_eq!(keys, [Cache::key(url), "untouched".to_string()]);
+
+    fs::write(cache.path_for(url).join("rfe-last-used"), "0").unwrap();
+    let pruned: Vec<_> = cache
+        .prune(Duration::from_secs(3600))
+        .unwrap()
+        .into_iter()
+        .map(|e| e.key)
+        .collect();
+    assert_eq!(pruned, [Cache::key(url)]);
+    assert!(cache_dir.path().join("untouched").exists());
+
+    cache.clear().unwrap();
+    assert!(cache_dir.path().join("my notes/todo.txt").exists());
+    assert!(cache_dir.path().join("not-a-repo").exists());
+    assert!(!cache_dir.path().join("untouched").exists());
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::commit_file;
+use git2::Repository;
+use repo_file_expander::catalog::{Catalog, TemplateFile};
+use repo_file_expander::{SourceContentReader, SourceOptions, SCAFFOLD_FILES};
+use std::fs;
+
+#[test]
+fn lists_every_template_with_a_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    for (path, contents) in [
+        (
+            "rust/rfe.toml",
+            "[template]\nname = \"rust\"\nversion = \"1.2.0\"\n\n[[files]]\nsource = \"nix/devenv.nix\"\ndestination = \"devenv.nix\"\ndescription = \"devenv configuration\"\n\n[[files]]\nsource = \".envrc\"\n",
+        ),
+        ("rust/nix/devenv.nix", "{ }\n"),
+        ("go/rfe.toml", "[template]\nname = \"go\"\n"),
+        ("README.md", "templates\n"),
+    ] {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    let reader = SourceContentReader::new(dir.path().to_str().unwrap()).unwrap();
+    let catalog = Catalog::of(&reader).unwrap();
+    assert_eq!(
+        catalog.files,
+        [
+            "README.md",
+            "go/rfe.toml",
+            "rust/nix/devenv.nix",
+            "rust/rfe.toml"
+        ]
+    );
+    let names: Vec<_> = catalog
+        .templates
+        .iter()
+        .map(|t| (t.name.as_deref(), t.subdir.as_deref()))
+        .collect();
+    assert_eq!(
+        names,
+        [(Some("go"), Some("go")), (Some("rust"), Some("rust"))]
+    );
+
+    let rust = &catalog.templates[1];
+    assert_eq!(rust.version.as_deref(), Some("1.2.0"));
+    assert_eq!(
+        rust.files,
+        [
+            TemplateFile {
+                source: "nix/devenv.nix".to_string(),
+                destination: "devenv.nix".to_string(),
+                description: Some("devenv configuration".to_string()),
+                embedded: false,
+            },
+            TemplateFile {
+                source: ".envrc".to_string(),
+                destination: ".envrc".to_string(),
+                description: None,
+                embedded: true,
+            },
+        ]
+    );
+    // Without [[files]] a template renders the default scaffold
+    let go: Vec<_> = catalog.templates[0]
+        .files
+        .iter()
+        .map(|f| f.source.as_str())
+        .collect();
+    assert_eq!(go, SCAFFOLD_FILES);
+}
+
+#[test]
+fn lists_a_git_repository_at_its_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    commit_file(&repo, "devenv.nix", "{ }\n");
+    fs::write(dir.path().join("uncommitted.txt"), "").unwrap();
+
+    let reader = SourceContentReader::open(
+        dir.path().to_str().unwrap(),
+        &SourceOptions {
+            git_ref: Some("HEAD".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let catalog = Catalog::of(&reader).unwrap();
+    assert_eq!(catalog.files, ["devenv.nix"]);
+    let template = &catalog.templates[0];
+    assert_eq!(template.name, None);
+    assert_eq!(template.subdir, None);
+    let embedded: Vec<_> = template
+        .files
+        .iter()
+        .filter(|f| f.embedded)
+        .map(|f| f.source.as_str())
+        .collect();
+    assert_eq!(embedded, ["devenv.yaml", ".gitignore", ".envrc"]);
+}
+
+#[test]
+fn lists_the_embedded_scaffold() {
+    let catalog = Catalog::of(&SourceContentReader::embedded()).unwrap();
+    assert_eq!(catalog.templates.len(), 1);
+    assert!(catalog.templates[0].files.iter().all(|f| !f.embedded));
+    assert!(catalog.files.contains(&"devenv.nix".to_string()));
+}
+
+#[test]
+fn broken_manifests_do_not_hide_other_templates() {
+    let dir = tempfile::tempdir().unwrap();
+    for (path, contents) in [
+        ("go/rfe.toml", "[template]\nnmae = \"go\"\n"),
+        ("rust/rfe.toml", "[template]\nname = \"rust\"\n"),
+    ] {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    let reader = SourceContentReader::new(dir.path().to_str().unwrap()).unwrap();
+    let catalog = Catalog::of(&reader).unwrap();
+    assert_eq!(catalog.templates.len(), 2);
+
+    let go = &catalog.templates[0];
+    assert_eq!(go.subdir.as_deref(), Some("go"));
+    assert!(go.files.is_empty());
+    assert!(go.error.as_deref().unwrap().contains("invalid rfe.toml"));
+
+    let rust = &catalog.templates[1];
+    assert_eq!(rust.name.as_deref(), Some("rust"));
+    assert_eq!(rust.error, None);
+}
@@ -0,0 +1,35 @@
+use std::fs;
+use std::process::{Command, Stdio};
+
+/// Run the `rfe` binary with `args`, without a terminal on stdin
+fn rfe(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rfe"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn init_keeps_existing_files_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(".gitignore"), "my-secret-rules\n").unwrap();
+
+    let output = rfe(&["init", dir.path().to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".gitignore")).unwrap(),
+        "my-secret-rules\n"
+    );
+    assert!(!dir.path().join(".gitignore.orig").exists());
+    assert!(dir.path().join("devenv.nix").exists());
+
+    // The file was never rfe's, so uninstall leaves it alone
+    let output = rfe(&["uninstall", dir.path().to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".gitignore")).unwrap(),
+        "my-secret-rules\n"
+    );
+}
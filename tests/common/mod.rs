@@ -0,0 +1,93 @@
+#![allow(dead_code)]
+
+use git2::{Oid, Repository, Signature};
+use repo_file_expander::lock::{LockedSource, LOCK_FILE};
+use repo_file_expander::{
+    render_scaffold, FileAction, Lockfile, RenderedFile, SourceContentReader, SourceOptions,
+    Variables, WritePlan,
+};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Write `contents` to `path` in the working tree and commit it on the
+/// current branch
+pub fn commit_file(repo: &Repository, path: &str, contents: &str) -> Oid {
+    let workdir = repo.workdir().unwrap();
+    let file_path = workdir.join(path);
+    fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+    fs::write(file_path, contents).unwrap();
+
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(path)).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+    let signature = Signature::now("rfe", "rfe@example.com").unwrap();
+    let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "update",
+        &tree,
+        &parents,
+    )
+    .unwrap()
+}
+
+/// Commit `contents` as devenv.nix
+pub fn commit(repo: &Repository, contents: &str) -> Oid {
+    commit_file(repo, "devenv.nix", contents)
+}
+
+/// A file at `path` rendered as `contents`
+pub fn rendered(path: &str, contents: &str) -> RenderedFile {
+    RenderedFile {
+        path: path.to_string(),
+        contents: contents.to_string(),
+    }
+}
+
+/// Write `files` into `target`, resolving conflicts with `action`, and
+/// record the lockfile as coming from `source`
+pub fn scaffold_into(
+    target: &Path,
+    source: LockedSource,
+    files: Vec<RenderedFile>,
+    action: FileAction,
+) {
+    let mut plan = WritePlan::new(target, files).unwrap();
+    plan.resolve_conflicts(|_| Ok(action)).unwrap();
+    plan.apply().unwrap();
+    Lockfile::record(source, None, &Variables::new(), &plan, None)
+        .save(&target.join(LOCK_FILE))
+        .unwrap();
+}
+
+/// A template repository committing `files` one by one, and a project that
+/// already holds `existing` scaffolded from it, resolving conflicts with
+/// `action`
+pub fn scaffold_template(
+    files: &[(&str, &str)],
+    existing: &[(&str, &str)],
+    action: FileAction,
+) -> (TempDir, Repository, TempDir) {
+    let template = tempfile::tempdir().unwrap();
+    let repo = Repository::init(template.path()).unwrap();
+    for (path, contents) in files {
+        commit_file(&repo, path, contents);
+    }
+
+    let project = tempfile::tempdir().unwrap();
+    for (path, contents) in existing {
+        fs::write(project.path().join(path), contents).unwrap();
+    }
+    let reader =
+        SourceContentReader::open(template.path().to_str().unwrap(), &SourceOptions::default())
+            .unwrap();
+    let files = render_scaffold(&reader, &Variables::new()).unwrap();
+    scaffold_into(project.path(), LockedSource::of(&reader), files, action);
+    (template, repo, project)
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use common::{commit, commit_file};
+use git2::Repository;
+use repo_file_expander::source::GitSource;
+use repo_file_expander::{
+    Provenance, RfeError, SourceContentReader, SourceOptions, TemplateSource,
+};
+use std::fs;
+use std::path::Path;
+
+fn open_at(path: &Path, git_ref: &str) -> repo_file_expander::Result<SourceContentReader> {
+    let options = SourceOptions {
+        git_ref: Some(git_ref.to_string()),
+        ..SourceOptions::default()
+    };
+    SourceContentReader::open(path.to_str().unwrap(), &options)
+}
+
+#[test]
+fn reads_at_tag_branch_and_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+
+    let first = commit(&repo, "{ version = 1; }\n");
+    let first_commit = repo.find_commit(first).unwrap();
+    repo.tag_lightweight("v1", first_commit.as_object(), false)
+        .unwrap();
+    repo.branch("old", &first_commit, false).unwrap();
+    let second = commit(&repo, "{ version = 2; }\n");
+
+    let head = SourceContentReader::new(dir.path().to_str().unwrap()).unwrap();
+    assert_eq!(head.revision(), Some(second.to_string()));
+
+    for git_ref in ["v1", "old", &first.to_string()[..8]] {
+        let reader = open_at(dir.path(), git_ref).unwrap();
+        assert_eq!(
+            reader.read_file_contents("devenv.nix").unwrap(),
+            "{ version = 1; }\n",
+            "{}",
+            git_ref
+        );
+        assert_eq!(reader.revision(), Some(first.to_string()));
+    }
+}
+
+#[test]
+fn unknown_ref_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    commit(&repo, "{ }\n");
+
+    let err = open_at(dir.path(), "does-not-exist").err().unwrap();
+    assert!(matches!(err, RfeError::RefNotFound { .. }));
+}
+
+#[test]
+fn ref_requires_a_git_source() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let err = open_at(dir.path(), "main").err().unwrap();
+    assert!(matches!(err, RfeError::RefUnsupported { .. }));
+    assert_eq!(err.exit_code(), 27);
+    assert!(err.to_string().contains("needs a git source"));
+}
+
+#[test]
+fn ref_ignores_uncommitted_edits() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let head = commit(&repo, "{ committed = true; }\n");
+    fs::write(dir.path().join("devenv.nix"), "{ committed = false; }\n").unwrap();
+
+    let reader = open_at(dir.path(), &head.to_string()).unwrap();
+    assert_eq!(
+        reader.read_file_contents("devenv.nix").unwrap(),
+        "{ committed = true; }\n"
+    );
+}
+
+#[test]
+fn reports_the_commit_a_file_was_read_at() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let head = commit(&repo, "{ }\n");
+
+    let reader = open_at(dir.path(), "HEAD").unwrap();
+    let (_, provenance) = reader.read_file("devenv.nix").unwrap();
+    assert_eq!(
+        provenance,
+        Provenance::Source {
+            path: "devenv.nix".to_string(),
+            source: reader.describe(),
+            commit: Some(head.to_string()),
+        }
+    );
+    let (_, provenance) = reader.read_file(".envrc").unwrap();
+    assert!(matches!(
+        provenance,
+        Provenance::Embedded {
+            missing_from: Some(_),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn remote_reads_blobs_without_checkout() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let head = commit(&repo, "{ remote = true; }\n");
+
+    let source = GitSource::remote(dir.path().to_str().unwrap(), None).unwrap();
+    assert_eq!(source.revision(), Some(head.to_string()));
+    assert_eq!(source.list_files().unwrap(), vec!["devenv.nix".to_string()]);
+    assert_eq!(
+        source.read_bytes("devenv.nix").unwrap().unwrap(),
+        b"{ remote = true; }\n"
+    );
+    assert_eq!(source.read_bytes("missing.nix").unwrap(), None);
+}
+
+#[test]
+fn subdir_of_a_git_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    commit_file(&repo, "templates/rust/devenv.nix", "{ rust = true; }\n");
+    let head = commit(&repo, "{ root = true; }\n");
+
+    let options = SourceOptions {
+        git_ref: Some(head.to_string()),
+        subdir: Some("templates/rust".to_string()),
+        ..SourceOptions::default()
+    };
+    let reader = SourceContentReader::open(dir.path().to_str().unwrap(), &options).unwrap();
+    assert_eq!(
+        reader.read_file_contents("devenv.nix").unwrap(),
+        "{ rust = true; }\n"
+    );
+    assert_eq!(reader.revision(), Some(head.to_string()));
+}
+
+#[test]
+fn dirty_working_trees_have_no_revision() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let head = commit(&repo, "{ committed = true; }\n");
+    let location = dir.path().to_str().unwrap();
+
+    let reader = SourceContentReader::new(location).unwrap();
+    assert_eq!(reader.revision(), Some(head.to_string()));
+
+    // The contents read are in no commit, so none is reported or recorded
+    fs::write(dir.path().join("devenv.nix"), "{ committed = false; }\n").unwrap();
+    let reader = SourceContentReader::new(location).unwrap();
+    assert_eq!(reader.revision(), None);
+    let (_, provenance) = reader.read_file("devenv.nix").unwrap();
+    assert!(matches!(
+        provenance,
+        Provenance::Source { commit: None, .. }
+    ));
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use common::commit;
+use git2::Repository;
+use repo_file_expander::cache::Cache;
+use repo_file_expander::source::{GitTransport, GitUrl};
+use repo_file_expander::{RfeError, SourceContentReader, SourceOptions};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+fn cached_options(cache_dir: &Path) -> SourceOptions {
+    SourceOptions {
+        cache: Some(Cache::new(cache_dir)),
+        ..SourceOptions::default()
+    }
+}
+
+/// Clone `upstream` into a bare repository at `path`
+fn bare_clone(upstream: &Path, path: &Path) {
+    git2::build::RepoBuilder::new()
+        .bare(true)
+        .clone(upstream.to_str().unwrap(), path)
+        .unwrap();
+}
+
+#[test]
+fn parses_urls_and_scp_syntax() {
+    let scp = GitUrl::parse("git@git.example.org:org/templates.git").unwrap();
+    assert_eq!(scp.transport, GitTransport::Ssh);
+    assert_eq!(scp.host.as_deref(), Some("git.example.org"));
+    assert_eq!(scp.path, "org/templates.git");
+
+    // Without a user, `host:` also parses as a URL scheme
+    for (remote, host) in [
+        ("git.example.org:org/templates.git", "git.example.org"),
+        ("gitea:org/templates.git", "gitea"),
+    ] {
+        let scp = GitUrl::parse(remote).unwrap();
+        assert_eq!(scp.transport, GitTransport::Ssh);
+        assert_eq!(scp.host.as_deref(), Some(host));
+        assert_eq!(scp.path, "org/templates.git");
+    }
+
+    let ssh = GitUrl::parse("ssh://git@git.example.org:2222/org/templates").unwrap();
+    assert_eq!(ssh.port, Some(2222));
+    assert_eq!(ssh.normalized(), "git.example.org:2222/org/templates");
+
+    let https = GitUrl::parse("https://gitea.internal/org/templates").unwrap();
+    assert_eq!(https.transport, GitTransport::Https);
+
+    let file = GitUrl::parse("file:///srv/git/templates.git").unwrap();
+    assert_eq!(file.transport, GitTransport::File);
+    assert_eq!(file.host, None);
+
+    assert_eq!(GitUrl::parse("../templates"), None);
+    assert_eq!(GitUrl::parse("C:\\templates"), None);
+    assert_eq!(GitUrl::parse("ftp://example.org/templates"), None);
+    assert_eq!(GitUrl::parse("https://example.org/"), None);
+}
+
+#[test]
+fn reads_file_urls_and_local_bare_repositories() {
+    let upstream = tempfile::tempdir().unwrap();
+    let repo = Repository::init(upstream.path()).unwrap();
+    let head = commit(&repo, "{ bare = true; }\n");
+
+    let bare = tempfile::tempdir().unwrap();
+    let bare_path = bare.path().join("templates.git");
+    bare_clone(upstream.path(), &bare_path);
+
+    let cache_dir = tempfile::tempdir().unwrap();
+    let url = format!("file://{}", bare_path.display());
+    let reader = SourceContentReader::open(&url, &cached_options(cache_dir.path())).unwrap();
+    assert_eq!(reader.revision(), Some(head.to_string()));
+    assert_eq!(
+        reader.read_file_contents("devenv.nix").unwrap(),
+        "{ bare = true; }\n"
+    );
+
+    let reader = SourceContentReader::new(bare_path.to_str().unwrap()).unwrap();
+    assert_eq!(reader.revision(), Some(head.to_string()));
+    assert_eq!(
+        reader.read_file_contents("devenv.nix").unwrap(),
+        "{ bare = true; }\n"
+    );
+}
+
+#[test]
+fn allowlist_rejects_other_hosts() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let options = SourceOptions {
+        allowed_hosts: vec!["git.example.org".to_string()],
+        ..cached_options(cache_dir.path())
+    };
+
+    let err = SourceContentReader::open("git@github.com:org/templates.git", &options)
+        .err()
+        .unwrap();
+    assert!(matches!(err, RfeError::HostNotAllowed { host } if host == "github.com"));
+}
+
+/// Kills the daemon when the test finishes
+struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn reads_from_git_daemon() {
+    let base = tempfile::tempdir().unwrap();
+    let upstream = tempfile::tempdir().unwrap();
+    let repo = Repository::init(upstream.path()).unwrap();
+    let head = commit(&repo, "{ daemon = true; }\n");
+    bare_clone(upstream.path(), &base.path().join("templates.git"));
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let spawned = Command::new("git")
+        .arg("daemon")
+        .arg("--export-all")
+        .arg("--reuseaddr")
+        .arg("--listen=127.0.0.1")
+        .arg(format!("--port={}", port))
+        .arg(format!("--base-path={}", base.path().display()))
+        .arg(base.path())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let _daemon = match spawned {
+        Ok(child) => Daemon(child),
+        Err(_) => {
+            eprintln!("git is not installed, skipping");
+            return;
+        }
+    };
+    let ready = (0..50).any(|_| {
+        thread::sleep(Duration::from_millis(100));
+        TcpStream::connect(("127.0.0.1", port)).is_ok()
+    });
+    assert!(ready, "git daemon did not start");
+
+    let cache_dir = tempfile::tempdir().unwrap();
+    let url = format!("git://127.0.0.1:{}/templates.git", port);
+    let reader = SourceContentReader::open(&url, &cached_options(cache_dir.path())).unwrap();
+    assert_eq!(reader.revision(), Some(head.to_string()));
+    assert_eq!(
+        reader.read_file_contents("devenv.nix").unwrap(),
+        "{ daemon = true; }\n"
+    );
+}
@@ -0,0 +1,184 @@
+use repo_file_expander::source::{EmbeddedSource, LocalDirectorySource};
+use repo_file_expander::{
+    render_scaffold, split_subdir, RfeError, SourceContentReader, SourceOptions, TemplateSource,
+    Variables, WritePlan, SCAFFOLD_FILES,
+};
+use std::fs;
+
+/// A source that serves a single in-memory file
+struct SingleFile;
+
+impl TemplateSource for SingleFile {
+    fn list_files(&self) -> repo_file_expander::Result<Vec<String>> {
+        Ok(vec!["devenv.nix".to_string()])
+    }
+
+    fn read_bytes(&self, path: &str) -> repo_file_expander::Result<Option<Vec<u8>>> {
+        Ok((path == "devenv.nix").then(|| b"{ }\n".to_vec()))
+    }
+
+    fn describe(&self) -> String {
+        "single file".to_string()
+    }
+}
+
+#[test]
+fn embedded_scaffold_has_every_file() {
+    let files = EmbeddedSource.list_files().unwrap();
+    for filename in SCAFFOLD_FILES {
+        assert!(files.contains(&filename.to_string()), "{}", filename);
+    }
+
+    let reader = SourceContentReader::embedded();
+    assert!(reader
+        .read_file_contents(".envrc")
+        .unwrap()
+        .contains("use devenv"));
+}
+
+#[test]
+fn local_directory_overrides_and_falls_back() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("devenv.nix"), "{ local = true; }\n").unwrap();
+
+    let reader = SourceContentReader::new(dir.path().to_str().unwrap()).unwrap();
+    assert_eq!(
+        reader.read_file_contents("devenv.nix").unwrap(),
+        "{ local = true; }\n"
+    );
+    assert_eq!(
+        reader.read_file_contents(".envrc").unwrap(),
+        SourceContentReader::embedded()
+            .read_file_contents(".envrc")
+            .unwrap()
+    );
+}
+
+#[test]
+fn missing_source_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("nope");
+
+    let err = SourceContentReader::new(missing.to_str().unwrap())
+        .err()
+        .unwrap();
+    assert!(matches!(err, RfeError::SourceNotFound { .. }));
+    assert_eq!(err.exit_code(), 3);
+}
+
+#[test]
+fn unknown_file_is_missing_everywhere() {
+    let reader = SourceContentReader::with_source(Box::new(SingleFile));
+    let err = reader.read_file_contents("flake.nix").unwrap_err();
+    assert!(matches!(err, RfeError::FileMissingEverywhere { .. }));
+}
+
+#[test]
+fn non_utf8_file_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("devenv.nix"), [0xff, 0xfe]).unwrap();
+
+    let reader = SourceContentReader::with_source(Box::new(LocalDirectorySource::new(dir.path())));
+    let err = reader.read_file_contents("devenv.nix").unwrap_err();
+    assert!(matches!(err, RfeError::NotUtf8 { .. }));
+}
+
+#[test]
+fn write_plan_creates_target_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("nested").join("project");
+
+    let reader = SourceContentReader::with_source(Box::new(SingleFile));
+    assert_eq!(reader.describe(), "single file");
+
+    let plan = WritePlan::new(
+        &target,
+        render_scaffold(&reader, &Variables::new()).unwrap(),
+    )
+    .unwrap();
+    let written = plan.apply().unwrap();
+
+    assert_eq!(written.len(), SCAFFOLD_FILES.len());
+    assert_eq!(
+        fs::read_to_string(target.join("devenv.nix")).unwrap(),
+        "{ }\n"
+    );
+    assert!(target.join(".gitignore").exists());
+}
+
+#[test]
+fn splits_subdir_shorthand() {
+    assert_eq!(
+        split_subdir("https://git.example.org/org/templates//rust"),
+        ("https://git.example.org/org/templates", Some("rust"))
+    );
+    assert_eq!(
+        split_subdir("git@git.example.org:org/templates.git//templates/rust"),
+        (
+            "git@git.example.org:org/templates.git",
+            Some("templates/rust")
+        )
+    );
+    assert_eq!(
+        split_subdir("https://git.example.org/org/templates"),
+        ("https://git.example.org/org/templates", None)
+    );
+    // A doubled slash in a local path is just a separator
+    assert_eq!(
+        split_subdir("/home/me//templates"),
+        ("/home/me//templates", None)
+    );
+    assert_eq!(split_subdir("templates//rust"), ("templates//rust", None));
+}
+
+#[test]
+fn subdir_roots_lookups() {
+    let dir = tempfile::tempdir().unwrap();
+    let rust = dir.path().join("templates").join("rust");
+    fs::create_dir_all(&rust).unwrap();
+    fs::write(rust.join("devenv.nix"), "{ rust = true; }\n").unwrap();
+    fs::write(dir.path().join("devenv.nix"), "{ root = true; }\n").unwrap();
+
+    let options = SourceOptions {
+        subdir: Some("templates/rust".to_string()),
+        ..SourceOptions::default()
+    };
+    let reader = SourceContentReader::open(dir.path().to_str().unwrap(), &options).unwrap();
+    assert_eq!(
+        reader.read_file_contents("devenv.nix").unwrap(),
+        "{ rust = true; }\n"
+    );
+
+    let missing = SourceOptions {
+        subdir: Some("templates/go".to_string()),
+        ..SourceOptions::default()
+    };
+    let err = SourceContentReader::open(dir.path().to_str().unwrap(), &missing)
+        .err()
+        .unwrap();
+    assert!(matches!(err, RfeError::SubdirNotFound { .. }));
+}
+
+#[test]
+fn subdirs_and_reads_stay_inside_the_source() {
+    let parent = tempfile::tempdir().unwrap();
+    let dir = parent.path().join("templates");
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("devenv.nix"), "{ }\n").unwrap();
+    fs::write(parent.path().join("secret.txt"), "secret\n").unwrap();
+
+    for subdir in ["..", "rust/../..", "/etc"] {
+        let options = SourceOptions {
+            subdir: Some(subdir.to_string()),
+            ..SourceOptions::default()
+        };
+        let err = SourceContentReader::open(dir.to_str().unwrap(), &options)
+            .err()
+            .unwrap();
+        assert_eq!(err.exit_code(), 25, "{}", subdir);
+    }
+
+    let source = LocalDirectorySource::new(&dir);
+    let err = source.read_bytes("../secret.txt").unwrap_err();
+    assert_eq!(err.exit_code(), 25);
+}
@@ -0,0 +1,195 @@
+mod common;
+
+use common::commit_file;
+use git2::Repository;
+use repo_file_expander::lock::{content_hash, LockedSource, LOCK_FILE, LOCK_VERSION};
+use repo_file_expander::{
+    render_scaffold, FileAction, Lockfile, RfeError, SourceContentReader, SourceOptions, Variables,
+    WritePlan,
+};
+use std::fs;
+
+const MANIFEST: &str = r#"
+[template]
+name = "rust"
+version = "1.2.0"
+
+[[files]]
+source = ".envrc"
+
+[[files]]
+source = "devenv.nix"
+
+[[variables]]
+name = "project_name"
+"#;
+
+#[test]
+fn records_source_commit_variables_and_hashes() {
+    let template = tempfile::tempdir().unwrap();
+    let repo = Repository::init(template.path()).unwrap();
+    commit_file(&repo, "rust/rfe.toml", MANIFEST);
+    commit_file(&repo, "rust/.envrc", "use devenv\n");
+    let head = commit_file(
+        &repo,
+        "rust/devenv.nix",
+        "{ name = \"{{ project_name }}\"; }\n",
+    );
+
+    let target = tempfile::tempdir().unwrap();
+    fs::write(target.path().join(".envrc"), "dotenv\n").unwrap();
+
+    let options = SourceOptions {
+        subdir: Some("rust".to_string()),
+        ..SourceOptions::default()
+    };
+    let reader = SourceContentReader::open(template.path().to_str().unwrap(), &options).unwrap();
+    let manifest = reader.manifest().unwrap();
+    let mut variables = Variables::new();
+    variables.set("project_name", "demo");
+    variables.set("editor", "vim");
+
+    let mut plan =
+        WritePlan::new(target.path(), render_scaffold(&reader, &variables).unwrap()).unwrap();
+    plan.resolve_conflicts(|_| Ok(FileAction::Merge)).unwrap();
+    plan.apply().unwrap();
+
+    let lock = Lockfile::record(
+        LockedSource::of(&reader),
+        manifest.as_ref(),
+        &variables,
+        &plan,
+        None,
+    );
+    let lock_path = target.path().join(LOCK_FILE);
+    lock.save(&lock_path).unwrap();
+    let loaded = Lockfile::load(&lock_path).unwrap();
+    assert_eq!(loaded, lock);
+
+    assert_eq!(
+        lock.source.url.as_deref(),
+        Some(fs::canonicalize(template.path()).unwrap().to_str().unwrap())
+    );
+    assert_eq!(lock.source.subdir.as_deref(), Some("rust"));
+    assert_eq!(lock.source.commit, Some(head.to_string()));
+    assert_eq!(lock.template.name.as_deref(), Some("rust"));
+    assert_eq!(lock.template.version.as_deref(), Some("1.2.0"));
+    assert_eq!(lock.variables().get("project_name"), Some("demo"));
+    // Only variables the manifest declares are recorded
+    assert_eq!(lock.variables().get("editor"), None);
+
+    let envrc = lock.file(".envrc").unwrap();
+    assert_eq!(envrc.action, FileAction::Merge);
+    assert_eq!(
+        envrc.sha256,
+        content_hash(&fs::read_to_string(target.path().join(".envrc")).unwrap())
+    );
+    assert_eq!(envrc.template_sha256, content_hash("use devenv\n"));
+
+    let nix = lock.file("devenv.nix").unwrap();
+    assert_eq!(nix.action, FileAction::Create);
+    assert_eq!(nix.sha256, content_hash("{ name = \"demo\"; }\n"));
+    assert_eq!(nix.sha256, nix.template_sha256);
+}
+
+#[test]
+fn rerunning_keeps_actions_and_user_files() {
+    let target = tempfile::tempdir().unwrap();
+    let reader = SourceContentReader::embedded();
+    let files = || render_scaffold(&reader, &Variables::new()).unwrap();
+
+    let plan = WritePlan::new(target.path(), files()).unwrap();
+    plan.apply().unwrap();
+    let first = Lockfile::record(
+        LockedSource::of(&reader),
+        None,
+        &Variables::new(),
+        &plan,
+        None,
+    );
+    assert_eq!(first.source, LockedSource::default());
+    assert_eq!(first.files.len(), 4);
+
+    // An edited file skipped on the second run stays as first recorded
+    fs::write(target.path().join(".envrc"), "edited\n").unwrap();
+    let mut plan = WritePlan::new(target.path(), files()).unwrap();
+    plan.resolve_conflicts(|_| Ok(FileAction::Skip)).unwrap();
+    let second = Lockfile::record(
+        LockedSource::of(&reader),
+        None,
+        &Variables::new(),
+        &plan,
+        Some(&first),
+    );
+    assert_eq!(second, first);
+}
+
+#[test]
+fn files_the_user_already_had_are_recorded_as_skipped() {
+    let target = tempfile::tempdir().unwrap();
+    let reader = SourceContentReader::embedded();
+    let files = || render_scaffold(&reader, &Variables::new()).unwrap();
+    let gitignore = reader.read_file_contents(".gitignore").unwrap();
+    fs::write(target.path().join(".gitignore"), &gitignore).unwrap();
+
+    let plan = WritePlan::new(target.path(), files()).unwrap();
+    plan.apply().unwrap();
+    let first = Lockfile::record(
+        LockedSource::of(&reader),
+        None,
+        &Variables::new(),
+        &plan,
+        None,
+    );
+    assert_eq!(first.version, LOCK_VERSION);
+    assert_eq!(first.file(".gitignore").unwrap().action, FileAction::Skip);
+    assert_eq!(first.file(".envrc").unwrap().action, FileAction::Create);
+
+    let plan = WritePlan::new(target.path(), files()).unwrap();
+    let second = Lockfile::record(
+        LockedSource::of(&reader),
+        None,
+        &Variables::new(),
+        &plan,
+        Some(&first),
+    );
+    assert_eq!(second, first);
+
+    // Entries carried over from a version 1 lockfile keep its version
+    let old = Lockfile {
+        version: 1,
+        ..first
+    };
+    let third = Lockfile::record(
+        LockedSource::of(&reader),
+        None,
+        &Variables::new(),
+        &plan,
+        Some(&old),
+    );
+    assert_eq!(third.version, 1);
+}
+
+#[test]
+fn records_local_sources_by_absolute_path() {
+    let template = tempfile::tempdir().unwrap();
+    fs::create_dir(template.path().join("rust")).unwrap();
+    fs::write(template.path().join("rust/devenv.nix"), "{ }\n").unwrap();
+
+    let location = template.path().join("rust/..");
+    let reader = SourceContentReader::new(location.to_str().unwrap()).unwrap();
+    let source = LockedSource::of(&reader);
+    let canonical = fs::canonicalize(template.path()).unwrap();
+    assert_eq!(source.url.as_deref(), Some(canonical.to_str().unwrap()));
+}
+
+#[test]
+fn rejects_lockfiles_from_newer_versions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(LOCK_FILE);
+    fs::write(&path, "version = 99\n[source]\n").unwrap();
+
+    let err = Lockfile::load(&path).unwrap_err();
+    assert!(matches!(err, RfeError::InvalidLockfile { .. }));
+    assert_eq!(err.exit_code(), 21);
+}
@@ -0,0 +1,96 @@
+use repo_file_expander::manifest::MANIFEST_FILE;
+use repo_file_expander::{render_scaffold, Manifest, RfeError, SourceContentReader, Variables};
+use std::fs;
+
+const MANIFEST: &str = r#"
+[template]
+name = "rust"
+version = "1.2.0"
+min_rfe_version = "0.1"
+
+[[files]]
+source = "nix/devenv.nix"
+destination = "devenv.nix"
+description = "devenv configuration"
+
+[[files]]
+source = ".envrc"
+
+[[variables]]
+name = "project_name"
+required = true
+
+[[variables]]
+name = "rust_channel"
+default = "stable"
+"#;
+
+#[test]
+fn parses_files_and_variables() {
+    let manifest = Manifest::parse(MANIFEST, "test").unwrap();
+    assert_eq!(manifest.template.name.as_deref(), Some("rust"));
+    assert_eq!(manifest.files[0].destination(), "devenv.nix");
+    assert_eq!(manifest.files[1].destination(), ".envrc");
+    assert!(manifest.variables[0].required);
+    assert_eq!(manifest.variables[1].default.as_deref(), Some("stable"));
+}
+
+#[test]
+fn rejects_unknown_keys_and_newer_templates() {
+    let err = Manifest::parse("[template]\nnmae = \"typo\"\n", "test").unwrap_err();
+    assert!(matches!(err, RfeError::InvalidManifest { .. }));
+
+    let err = Manifest::parse("[template]\nmin_rfe_version = \"99.0.0\"\n", "test").unwrap_err();
+    assert!(matches!(err, RfeError::IncompatibleTemplate { .. }));
+}
+
+#[test]
+fn rejects_paths_outside_the_template_or_target() {
+    for files in [
+        "source = \"devenv.nix\"\ndestination = \"../escaped.txt\"",
+        "source = \"devenv.nix\"\ndestination = \"/etc/escaped.txt\"",
+        "source = \"../secrets.nix\"",
+    ] {
+        let err = Manifest::parse(&format!("[[files]]\n{}\n", files), "test").unwrap_err();
+        assert!(matches!(err, RfeError::InvalidManifest { .. }), "{}", files);
+    }
+}
+
+#[test]
+fn rendered_destinations_must_stay_in_the_target() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("devenv.nix"), "{ }\n").unwrap();
+    fs::write(
+        dir.path().join(MANIFEST_FILE),
+        "[[files]]\nsource = \"devenv.nix\"\ndestination = \"{{ dir }}/escaped.nix\"\n",
+    )
+    .unwrap();
+    let reader = SourceContentReader::new(dir.path().to_str().unwrap()).unwrap();
+
+    for dir in ["..", ""] {
+        let mut variables = Variables::new();
+        variables.set("dir", dir);
+        let err = render_scaffold(&reader, &variables).unwrap_err();
+        assert_eq!(err.exit_code(), 25);
+    }
+}
+
+#[test]
+fn manifest_drives_rendered_files() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("nix")).unwrap();
+    fs::write(dir.path().join("nix/devenv.nix"), "{ nested = true; }\n").unwrap();
+    fs::write(dir.path().join(MANIFEST_FILE), MANIFEST).unwrap();
+
+    let reader = SourceContentReader::new(dir.path().to_str().unwrap()).unwrap();
+    let files = render_scaffold(&reader, &Variables::new()).unwrap();
+
+    let paths: Vec<_> = files.iter().map(|file| file.path.as_str()).collect();
+    assert_eq!(paths, ["devenv.nix", ".envrc"]);
+    assert_eq!(files[0].contents, "{ nested = true; }\n");
+}
+
+#[test]
+fn embedded_scaffold_has_no_manifest() {
+    assert_eq!(SourceContentReader::embedded().manifest().unwrap(), None);
+}
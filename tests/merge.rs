@@ -0,0 +1,353 @@
+use repo_file_expander::merge::{merge, strip_managed_block};
+
+const TEMPLATE_GITIGNORE: &str = "\
+# Devenv
+.devenv*
+devenv.local.nix
+
+# direnv
+.direnv
+";
+
+#[test]
+fn appends_missing_gitignore_patterns_in_a_managed_block() {
+    let existing = "target/\n**/.direnv\n";
+    let merged = merge(".gitignore", existing, TEMPLATE_GITIGNORE);
+
+    assert_eq!(
+        merged.contents,
+        "target/\n**/.direnv\n\n# rfe-managed begin\n.devenv*\ndevenv.local.nix\n# rfe-managed end\n"
+    );
+    assert!(merged.conflicts.is_empty());
+}
+
+#[test]
+fn updates_the_gitignore_block_in_place() {
+    let existing = "\
+target/
+# rfe-managed begin
+.devenv*
+.pre-commit-config.yaml
+# rfe-managed end
+*.log
+";
+    let merged = merge(".gitignore", existing, TEMPLATE_GITIGNORE);
+    assert_eq!(
+        merged.contents,
+        "\
+target/
+# rfe-managed begin
+.devenv*
+devenv.local.nix
+.direnv
+# rfe-managed end
+*.log
+"
+    );
+
+    let again = merge(".gitignore", &merged.contents, TEMPLATE_GITIGNORE);
+    assert_eq!(again.contents, merged.contents);
+}
+
+#[test]
+fn treats_equivalent_gitignore_patterns_as_present() {
+    let existing = "/.devenv*\ndevenv.local.nix  \n.direnv/**\n";
+    let template = ".devenv*\n**/devenv.local.nix\n.direnv/\n/build/out\nbuild/out\n";
+    let merged = merge(".gitignore", existing, template);
+
+    // `/.devenv*` is anchored to the root and so not the same as `.devenv*`
+    assert_eq!(
+        merged.contents,
+        "/.devenv*\ndevenv.local.nix  \n.direnv/**\n\n# rfe-managed begin\n.devenv*\n/build/out\n# rfe-managed end\n"
+    );
+}
+
+#[test]
+fn gitignore_already_covered_gets_no_block() {
+    let existing = ".devenv*\ndevenv.local.nix\n.direnv\n";
+    let merged = merge(".gitignore", existing, TEMPLATE_GITIGNORE);
+    assert_eq!(merged.contents, existing);
+}
+
+#[test]
+fn strips_the_managed_block() {
+    let contents = "target/\n\n# rfe-managed begin\n.devenv*\n# rfe-managed end\n";
+    assert_eq!(strip_managed_block(contents), "target/\n");
+}
+
+const TEMPLATE_ENVRC: &str = "\
+source_url \"https://example.org/direnvrc\" \"sha256-abc\"
+
+use devenv
+";
+
+#[test]
+fn keeps_envrc_directives_and_adds_template_lines() {
+    let existing = "source_up\ndotenv\nPATH_add bin\n";
+    let merged = merge(".envrc", existing, TEMPLATE_ENVRC);
+
+    assert_eq!(
+        merged.contents,
+        "source_up\ndotenv\nPATH_add bin\n\n# rfe-managed begin\nsource_url \"https://example.org/direnvrc\" \"sha256-abc\"\nuse devenv\n# rfe-managed end\n"
+    );
+    assert!(merged.conflicts.is_empty());
+
+    let again = merge(".envrc", &merged.contents, TEMPLATE_ENVRC);
+    assert_eq!(again.contents, merged.contents);
+}
+
+#[test]
+fn does_not_repeat_use_statements() {
+    let existing = "dotenv\nuse devenv --impure\n";
+    let merged = merge(".envrc", existing, TEMPLATE_ENVRC);
+
+    assert_eq!(
+        merged.contents,
+        "dotenv\nuse devenv --impure\n\n# rfe-managed begin\nsource_url \"https://example.org/direnvrc\" \"sha256-abc\"\n# rfe-managed end\n"
+    );
+    assert_eq!(
+        merged.conflicts,
+        ["keeping `use devenv --impure` instead of the template's `use devenv`"]
+    );
+}
+
+#[test]
+fn updates_the_envrc_block_in_place() {
+    let existing = "\
+# rfe-managed begin
+source_url \"https://example.org/direnvrc\" \"sha256-old\"
+use devenv
+# rfe-managed end
+dotenv
+";
+    let merged = merge(".envrc", existing, TEMPLATE_ENVRC);
+    assert_eq!(
+        merged.contents,
+        "\
+# rfe-managed begin
+source_url \"https://example.org/direnvrc\" \"sha256-abc\"
+use devenv
+# rfe-managed end
+dotenv
+"
+    );
+}
+
+#[test]
+fn reports_use_statements_for_other_layouts() {
+    let merged = merge(".envrc", "use flake\n", "use devenv\n");
+    assert_eq!(
+        merged.contents,
+        "use flake\n\n# rfe-managed begin\nuse devenv\n# rfe-managed end\n"
+    );
+    assert_eq!(
+        merged.conflicts,
+        ["adding `use devenv` next to `use flake`"]
+    );
+}
+
+const TEMPLATE_YAML: &str = "\
+inputs:
+  nixpkgs:
+    url: github:cachix/devenv-nixpkgs/rolling
+  fenix:
+    url: github:nix-community/fenix
+    inputs:
+      nixpkgs:
+        follows: nixpkgs
+imports:
+  - ./backend
+";
+
+#[test]
+fn unions_devenv_yaml_inputs_and_imports() {
+    let existing = "\
+# project settings
+allowUnfree: true
+inputs:
+  # pinned for the CI image
+  nixpkgs:
+    url: github:NixOS/nixpkgs/nixos-24.05
+  rust-overlay:
+    url: github:oxalica/rust-overlay
+
+imports:
+- ./frontend
+";
+    let merged = merge("devenv.yaml", existing, TEMPLATE_YAML);
+
+    assert_eq!(
+        merged.contents,
+        "\
+# project settings
+allowUnfree: true
+inputs:
+  # pinned for the CI image
+  nixpkgs:
+    url: github:NixOS/nixpkgs/nixos-24.05
+  rust-overlay:
+    url: github:oxalica/rust-overlay
+  fenix:
+    url: github:nix-community/fenix
+    inputs:
+      nixpkgs:
+        follows: nixpkgs
+
+imports:
+- ./frontend
+- ./backend
+"
+    );
+    assert_eq!(
+        merged.conflicts,
+        ["input nixpkgs: keeping url github:NixOS/nixpkgs/nixos-24.05, the template uses github:cachix/devenv-nixpkgs/rolling"]
+    );
+
+    let again = merge("devenv.yaml", &merged.contents, TEMPLATE_YAML);
+    assert_eq!(again.contents, merged.contents);
+}
+
+#[test]
+fn adds_missing_devenv_yaml_sections() {
+    let existing = "allowUnfree: true\ninputs: {}\n";
+    let merged = merge("devenv.yaml", existing, TEMPLATE_YAML);
+
+    assert_eq!(
+        merged.contents,
+        "\
+allowUnfree: true
+inputs:
+  nixpkgs:
+    url: github:cachix/devenv-nixpkgs/rolling
+  fenix:
+    url: github:nix-community/fenix
+    inputs:
+      nixpkgs:
+        follows: nixpkgs
+
+imports:
+  - ./backend
+"
+    );
+    assert!(merged.conflicts.is_empty());
+}
+
+#[test]
+fn invalid_devenv_yaml_falls_back_to_a_line_merge() {
+    let merged = merge("devenv.yaml", "inputs: [\n", TEMPLATE_YAML);
+    assert_eq!(merged.conflicts[0], "not valid YAML, merged line by line");
+}
+
+const TEMPLATE_NIX: &str = r#"{ pkgs, ... }: {
+  env.GREET = "devenv";
+
+  packages = [ pkgs.git pkgs.jq ];
+
+  languages.rust = {
+    enable = true;
+    channel = "stable";
+  };
+
+  services.postgres.enable = true;
+
+  enterShell = ''
+    git --version
+  '';
+}
+"#;
+
+#[test]
+fn merges_devenv_nix_attributes() {
+    let existing = r#"{ pkgs, lib, ... }:
+
+{
+  # project specific
+  env = {
+    DATABASE_URL = "postgres://localhost/app";
+  };
+
+  packages = with pkgs; [
+    git
+    curl
+  ];
+
+  languages.rust.enable = true;
+
+  enterShell = "echo hi";
+}
+"#;
+    let merged = merge("devenv.nix", existing, TEMPLATE_NIX);
+
+    assert_eq!(
+        merged.contents,
+        r#"{ pkgs, lib, ... }:
+
+{
+  # project specific
+  env = {
+    DATABASE_URL = "postgres://localhost/app";
+    GREET = "devenv";
+  };
+
+  packages = with pkgs; [
+    git
+    curl
+    pkgs.jq
+  ];
+
+  languages.rust.enable = true;
+  languages.rust.channel = "stable";
+
+  enterShell = "echo hi";
+  services.postgres.enable = true;
+}
+"#
+    );
+    assert_eq!(
+        merged.conflicts,
+        ["enterShell: keeping `\"echo hi\"`, the template sets `'' git --version ''`"]
+    );
+
+    let again = merge("devenv.nix", &merged.contents, TEMPLATE_NIX);
+    assert_eq!(again.contents, merged.contents);
+}
+
+#[test]
+fn adds_whole_blocks_to_devenv_nix() {
+    let existing = "{ pkgs, ... }: {\n  packages = [ pkgs.git ];\n}\n";
+    let merged = merge("devenv.nix", existing, TEMPLATE_NIX);
+
+    assert_eq!(
+        merged.contents,
+        r#"{ pkgs, ... }: {
+  packages = [ pkgs.git pkgs.jq ];
+  env.GREET = "devenv";
+  languages.rust = {
+    enable = true;
+    channel = "stable";
+  };
+  services.postgres.enable = true;
+  enterShell = ''
+    git --version
+  '';
+}
+"#
+    );
+    assert!(merged.conflicts.is_empty());
+}
+
+#[test]
+fn reports_devenv_nix_values_that_cannot_be_merged() {
+    let existing = "{ ... }: {\n  env = import ./env.nix;\n}\n";
+    let merged = merge(
+        "devenv.nix",
+        existing,
+        "{ ... }: {\n  env.GREET = \"hi\";\n}\n",
+    );
+
+    assert_eq!(merged.contents, existing);
+    assert_eq!(
+        merged.conflicts,
+        ["env.GREET: not added, env is not an attribute set in the existing file"]
+    );
+}
@@ -0,0 +1,214 @@
+mod common;
+
+use common::rendered;
+use repo_file_expander::merge::merge_lines;
+use repo_file_expander::{FileAction, WritePlan};
+use std::fs;
+
+#[test]
+fn plans_create_overwrite_and_skip() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("same.txt"), "same\n").unwrap();
+    fs::write(dir.path().join("changed.txt"), "old\n").unwrap();
+
+    let plan = WritePlan::new(
+        dir.path(),
+        vec![
+            rendered("new.txt", "new\n"),
+            rendered("same.txt", "same\n"),
+            rendered("changed.txt", "new\n"),
+        ],
+    )
+    .unwrap();
+
+    let actions: Vec<_> = plan.files().iter().map(|file| file.action).collect();
+    assert_eq!(
+        actions,
+        [FileAction::Create, FileAction::Skip, FileAction::Overwrite]
+    );
+    assert_eq!(plan.files()[2].existing.as_deref(), Some("old\n"));
+}
+
+#[test]
+fn diffs_against_existing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("devenv.nix"), "{\n  a = 1;\n}\n").unwrap();
+
+    let plan = WritePlan::new(
+        dir.path(),
+        vec![
+            rendered("devenv.nix", "{\n  a = 2;\n}\n"),
+            rendered(".envrc", "use devenv\n"),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(
+        plan.files()[0].diff(),
+        "--- a/devenv.nix\n+++ b/devenv.nix\n@@ -1,3 +1,3 @@\n {\n-  a = 1;\n+  a = 2;\n }\n"
+    );
+    assert_eq!(
+        plan.files()[1].diff(),
+        "--- /dev/null\n+++ b/.envrc\n@@ -0,0 +1 @@\n+use devenv\n"
+    );
+}
+
+#[test]
+fn planning_writes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("project");
+
+    let plan = WritePlan::new(&target, vec![rendered("devenv.yaml", "inputs: {}\n")]).unwrap();
+
+    assert_eq!(plan.files()[0].action, FileAction::Create);
+    assert!(!target.exists());
+
+    let written = plan.apply().unwrap();
+    assert_eq!(written, [target.join("devenv.yaml")]);
+    assert!(plan.files()[0].diff().contains("+inputs: {}"));
+}
+
+#[test]
+fn apply_leaves_skipped_files_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(".gitignore"), ".devenv*\n").unwrap();
+
+    let plan = WritePlan::new(dir.path(), vec![rendered(".gitignore", ".devenv*\n")]).unwrap();
+
+    assert!(plan.files()[0].diff().is_empty());
+    assert!(plan.apply().unwrap().is_empty());
+}
+
+fn conflicting_plan(dir: &std::path::Path, action: FileAction) -> WritePlan {
+    fs::write(dir.join(".envrc"), "dotenv\nuse flake\n").unwrap();
+    let mut plan = WritePlan::new(
+        dir,
+        vec![
+            rendered(".envrc", "use devenv\n"),
+            rendered("devenv.yaml", "inputs: {}\n"),
+        ],
+    )
+    .unwrap();
+    plan.resolve_conflicts(|_| Ok(action)).unwrap();
+    plan
+}
+
+#[test]
+fn conflict_policy_only_applies_to_existing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let plan = conflicting_plan(dir.path(), FileAction::Skip);
+
+    assert_eq!(plan.files()[0].action, FileAction::Skip);
+    assert_eq!(plan.files()[1].action, FileAction::Create);
+    plan.apply().unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".envrc")).unwrap(),
+        "dotenv\nuse flake\n"
+    );
+    assert!(dir.path().join("devenv.yaml").exists());
+}
+
+#[test]
+fn backup_keeps_the_first_original_and_rotates() {
+    let dir = tempfile::tempdir().unwrap();
+    let plan = conflicting_plan(dir.path(), FileAction::Backup);
+    let written = plan.apply().unwrap();
+
+    assert_eq!(written[0], dir.path().join(".envrc.orig"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".envrc.orig")).unwrap(),
+        "dotenv\nuse flake\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".envrc")).unwrap(),
+        "use devenv\n"
+    );
+
+    fs::write(dir.path().join(".envrc"), "edited\n").unwrap();
+    let mut plan = WritePlan::new(dir.path(), vec![rendered(".envrc", "use devenv\n")]).unwrap();
+    plan.resolve_conflicts(|_| Ok(FileAction::Backup)).unwrap();
+    let written = plan.apply().unwrap();
+    assert_eq!(written[0], dir.path().join(".envrc.orig.1"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".envrc.orig")).unwrap(),
+        "dotenv\nuse flake\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".envrc.orig.1")).unwrap(),
+        "edited\n"
+    );
+}
+
+#[test]
+fn backup_copies_the_original_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let original = b"latin-1 caf\xe9\n".to_vec();
+    fs::write(dir.path().join("notes.txt"), &original).unwrap();
+
+    let mut plan = WritePlan::new(dir.path(), vec![rendered("notes.txt", "cafe\n")]).unwrap();
+    plan.resolve_conflicts(|_| Ok(FileAction::Backup)).unwrap();
+    plan.apply().unwrap();
+
+    assert_eq!(
+        fs::read(dir.path().join("notes.txt.orig")).unwrap(),
+        original
+    );
+}
+
+#[test]
+fn merge_keeps_user_lines_and_marks_conflicts() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("Procfile"),
+        "web: run
+worker: old
+",
+    )
+    .unwrap();
+    let mut plan = WritePlan::new(
+        dir.path(),
+        vec![rendered(
+            "Procfile",
+            "web: run
+worker: new
+",
+        )],
+    )
+    .unwrap();
+    plan.resolve_conflicts(|_| Ok(FileAction::Merge)).unwrap();
+    let file = &plan.files()[0];
+
+    assert_eq!(
+        file.result(),
+        "web: run\n<<<<<<< existing\nworker: old\n=======\nworker: new\n>>>>>>> template\n"
+    );
+    assert_eq!(
+        file.merged().unwrap().conflicts,
+        ["line 2 differs from the template"]
+    );
+}
+
+#[test]
+fn merge_adds_template_lines_without_conflicts() {
+    let merged = merge_lines("a\nlocal\nb\n", "a\nb\nc\n");
+    assert_eq!(merged.contents, "a\nlocal\nb\nc\n");
+    assert!(merged.conflicts.is_empty());
+}
+
+#[test]
+fn refuses_to_write_outside_the_target() {
+    let parent = tempfile::tempdir().unwrap();
+    let target = parent.path().join("project");
+    fs::create_dir(&target).unwrap();
+
+    for path in [
+        "../escaped.txt",
+        "/tmp/escaped.txt",
+        "nested/../../escaped.txt",
+    ] {
+        let plan = WritePlan::new(&target, vec![rendered(path, "x\n")]).unwrap();
+        let err = plan.apply().unwrap_err();
+        assert_eq!(err.exit_code(), 25, "{}", path);
+    }
+    assert!(!parent.path().join("escaped.txt").exists());
+}
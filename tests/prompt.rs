@@ -0,0 +1,121 @@
+use repo_file_expander::manifest::MANIFEST_FILE;
+use repo_file_expander::prompt::Prompter;
+use repo_file_expander::{FileAction, Manifest, RenderedFile, RfeError, Variables, WritePlan};
+use std::fs;
+use std::io::Cursor;
+
+const MANIFEST: &str = r#"
+[[variables]]
+name = "project_name"
+description = "Project name"
+required = true
+pattern = "[a-z][a-z0-9-]*"
+
+[[variables]]
+name = "database"
+type = "choice"
+choices = ["postgres", "mysql"]
+default = "postgres"
+
+[[variables]]
+name = "rust"
+type = "boolean"
+default = "no"
+
+[[variables]]
+name = "owner"
+"#;
+
+fn manifest() -> Manifest {
+    Manifest::parse(MANIFEST, MANIFEST_FILE).unwrap()
+}
+
+#[test]
+fn prompts_until_answers_are_valid() {
+    let input = "\nMy App\nmy-app\nsqlite\nmysql\nyes\n\n";
+    let mut output = Vec::new();
+    let answers = Prompter::new(Cursor::new(input), &mut output)
+        .ask_missing(&manifest(), &Variables::new())
+        .unwrap();
+
+    assert_eq!(answers.get("project_name"), Some("my-app"));
+    assert_eq!(answers.get("database"), Some("mysql"));
+    assert_eq!(answers.get("rust"), Some("true"));
+    assert_eq!(answers.get("owner"), None);
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("project_name is required"));
+    assert!(output.contains("My App does not match"));
+    assert!(output.contains("(postgres/mysql) [postgres]"));
+}
+
+#[test]
+fn known_variables_are_not_prompted_for() {
+    let mut known = Variables::new();
+    known.set("project_name", "demo");
+
+    let answers = Prompter::new(Cursor::new("\n\n\n"), Vec::new())
+        .ask_missing(&manifest(), &known)
+        .unwrap();
+    assert_eq!(answers.get("project_name"), None);
+    assert_eq!(answers.get("database"), Some("postgres"));
+    assert_eq!(answers.get("rust"), Some("false"));
+}
+
+#[test]
+fn end_of_input_is_an_error() {
+    let err = Prompter::new(Cursor::new(""), Vec::new())
+        .ask_missing(&manifest(), &Variables::new())
+        .unwrap_err();
+    assert!(matches!(err, RfeError::Io { .. }));
+}
+
+#[test]
+fn provided_values_are_validated() {
+    let assignments = [
+        ("project_name".to_string(), "demo".to_string()),
+        ("database".to_string(), "oracle".to_string()),
+    ];
+    let err = Variables::resolve(Some(&manifest()), Variables::new(), &assignments).unwrap_err();
+    assert!(matches!(err, RfeError::InvalidAnswer { name, .. } if name == "database"));
+}
+
+#[test]
+fn loads_answers_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("answers.toml");
+    fs::write(&path, "project_name = \"demo\"\nrust = true\n").unwrap();
+
+    let loaded = Variables::load(&path).unwrap();
+    assert_eq!(loaded.get("rust"), Some("true"));
+
+    let resolved = Variables::resolve(Some(&manifest()), loaded, &[]).unwrap();
+    assert_eq!(resolved.get("project_name"), Some("demo"));
+
+    fs::write(&path, "nested = { a = 1 }\n").unwrap();
+    assert!(matches!(
+        Variables::load(&path),
+        Err(RfeError::InvalidAnswersFile { .. })
+    ));
+}
+
+#[test]
+fn asks_what_to_do_with_conflicting_files() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(".envrc"), "use flake\n").unwrap();
+    let file = RenderedFile {
+        path: ".envrc".to_string(),
+        contents: "use devenv\n".to_string(),
+    };
+    let mut plan = WritePlan::new(dir.path(), vec![file]).unwrap();
+
+    let mut output = Vec::new();
+    let mut prompter = Prompter::new(Cursor::new("x\nb\n"), &mut output);
+    plan.resolve_conflicts(|file| prompter.choose_action(file))
+        .unwrap();
+
+    assert_eq!(plan.files()[0].action, FileAction::Backup);
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("-use flake\n+use devenv\n"));
+    assert!(output.contains("x is not one of o, s, b, m"));
+}
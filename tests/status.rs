@@ -0,0 +1,125 @@
+mod common;
+
+use common::{commit_file, scaffold_template};
+use git2::Repository;
+use repo_file_expander::status::{FileState, FileStatus, ProjectStatus};
+use repo_file_expander::{render_scaffold, FileAction, Lockfile, SourceOptions};
+use std::fs;
+use std::path::Path;
+
+const MANIFEST: &str = "[[files]]\nsource = \"devenv.nix\"\n\n[[files]]\nsource = \".envrc\"\n";
+
+/// A template repository and a project scaffolded from it
+fn scaffold() -> (tempfile::TempDir, Repository, tempfile::TempDir) {
+    scaffold_template(
+        &[
+            ("rfe.toml", MANIFEST),
+            (".envrc", "use devenv\n"),
+            (
+                "devenv.nix",
+                "{ pkgs, ... }: {\n  packages = [ pkgs.git ];\n}\n",
+            ),
+        ],
+        &[],
+        FileAction::Skip,
+    )
+}
+
+/// Compare `project` with the template's current commit, as `rfe status` does
+fn status(project: &Path) -> ProjectStatus {
+    let lock = Lockfile::find(project).unwrap();
+    let reader = lock.source.open(None, &SourceOptions::default()).unwrap();
+    let next = render_scaffold(&reader, &lock.variables()).unwrap();
+    ProjectStatus::new(project, &lock, &next).unwrap()
+}
+
+fn state(status: &ProjectStatus, path: &str) -> FileState {
+    status
+        .files()
+        .iter()
+        .find(|file| file.path == path)
+        .unwrap()
+        .state
+}
+
+#[test]
+fn freshly_scaffolded_projects_are_clean() {
+    let (_template, _repo, project) = scaffold();
+
+    let status = status(project.path());
+    assert!(status.is_clean());
+    assert_eq!(status.files().len(), 2);
+}
+
+#[test]
+fn reports_local_changes() {
+    let (_template, _repo, project) = scaffold();
+    fs::write(project.path().join(".envrc"), "use flake\n").unwrap();
+    fs::remove_file(project.path().join("devenv.nix")).unwrap();
+
+    let status = status(project.path());
+    assert!(!status.is_clean());
+    assert_eq!(state(&status, ".envrc"), FileState::Modified);
+    assert_eq!(state(&status, "devenv.nix"), FileState::Missing);
+}
+
+#[test]
+fn reports_template_changes() {
+    let (_template, repo, project) = scaffold();
+    commit_file(&repo, ".envrc", "use devenv\ndotenv\n");
+    commit_file(
+        &repo,
+        "devenv.nix",
+        "{ pkgs, ... }: {\n  packages = [ pkgs.jq ];\n}\n",
+    );
+    fs::write(project.path().join("devenv.nix"), "{ }\n").unwrap();
+
+    let status = status(project.path());
+    assert_eq!(
+        status.files(),
+        [
+            FileStatus {
+                path: "devenv.nix".to_string(),
+                state: FileState::Modified,
+                template_changed: true,
+            },
+            FileStatus {
+                path: ".envrc".to_string(),
+                state: FileState::Outdated,
+                template_changed: true,
+            },
+        ]
+    );
+}
+
+#[test]
+fn reports_files_the_template_gained_or_dropped() {
+    let (_template, repo, project) = scaffold();
+    commit_file(
+        &repo,
+        "rfe.toml",
+        "[[files]]\nsource = \"devenv.nix\"\n\n[[files]]\nsource = \"devenv.yaml\"\n",
+    );
+    commit_file(&repo, "devenv.yaml", "inputs: {}\n");
+
+    let status = status(project.path());
+    assert_eq!(state(&status, "devenv.nix"), FileState::Unmodified);
+    assert_eq!(state(&status, ".envrc"), FileState::Outdated);
+    assert_eq!(state(&status, "devenv.yaml"), FileState::Outdated);
+}
+
+#[test]
+fn serializes_for_ci() {
+    let (_template, _repo, project) = scaffold();
+    fs::write(project.path().join(".envrc"), "use flake\n").unwrap();
+
+    let json = serde_json::to_value(status(project.path())).unwrap();
+    assert_eq!(
+        json["files"][1],
+        serde_json::json!({
+            "path": ".envrc",
+            "state": "modified",
+            "template_changed": false,
+        })
+    );
+}
@@ -0,0 +1,161 @@
+use repo_file_expander::manifest::MANIFEST_FILE;
+use repo_file_expander::template::render;
+use repo_file_expander::{
+    render_file, render_scaffold, Manifest, Provenance, RfeError, SourceContentReader, Variables,
+};
+use std::fs;
+
+fn variables(pairs: &[(&str, &str)]) -> Variables {
+    let mut variables = Variables::new();
+    for (name, value) in pairs {
+        variables.set(*name, *value);
+    }
+    variables
+}
+
+#[test]
+fn substitutes_placeholders_conditionals_loops_and_filters() {
+    let template = r#"{
+  env.PROJECT = "{{ project_name | snake_case }}";
+  env.CONST = "{{ project_name | snake_case | upper }}";
+{% if rust == "true" %}  languages.rust.enable = true;
+{% endif %}  packages = [{% for p in packages | split(",") %} pkgs.{{ p }}{% endfor %} ];
+}
+"#;
+    let vars = variables(&[
+        ("project_name", "My Service"),
+        ("rust", "true"),
+        ("packages", "git,openssl"),
+    ]);
+
+    assert_eq!(
+        render("devenv.nix", template, &vars).unwrap(),
+        r#"{
+  env.PROJECT = "my_service";
+  env.CONST = "MY_SERVICE";
+  languages.rust.enable = true;
+  packages = [ pkgs.git pkgs.openssl ];
+}
+"#
+    );
+}
+
+#[test]
+fn unresolved_placeholders_are_errors() {
+    let err = render("devenv.nix", "{{ missing }}\n", &Variables::new()).unwrap_err();
+    assert!(matches!(err, RfeError::Template { file, .. } if file == "devenv.nix"));
+}
+
+#[test]
+fn nix_interpolation_is_left_alone() {
+    let contents = "{ pkgs, ... }: {\n  env.A = \"${pkgs.git}\";\n}\n";
+    assert_eq!(
+        render("devenv.nix", contents, &Variables::new()).unwrap(),
+        contents
+    );
+}
+
+#[test]
+fn assignments_override_environment_and_defaults() {
+    let manifest = Manifest::parse(
+        r#"
+[[variables]]
+name = "channel"
+default = "stable"
+
+[[variables]]
+name = "owner"
+default = "nobody"
+
+[[variables]]
+name = "project_name"
+required = true
+"#,
+        "test",
+    )
+    .unwrap();
+
+    let err = Variables::resolve(Some(&manifest), Variables::new(), &[]).unwrap_err();
+    assert!(matches!(err, RfeError::MissingVariables { names } if names == ["project_name"]));
+
+    let environment = variables(&[("owner", "platform"), ("channel", "beta")]);
+    let assignments = [
+        Variables::parse_assignment("project_name=demo").unwrap(),
+        Variables::parse_assignment("channel=nightly").unwrap(),
+    ];
+    let resolved = Variables::resolve(Some(&manifest), environment, &assignments).unwrap();
+    assert_eq!(resolved.get("channel"), Some("nightly"));
+    assert_eq!(resolved.get("owner"), Some("platform"));
+    assert_eq!(resolved.get("project_name"), Some("demo"));
+
+    assert!(matches!(
+        Variables::parse_assignment("=oops"),
+        Err(RfeError::InvalidVariable { .. })
+    ));
+}
+
+#[test]
+fn renders_file_contents_and_destinations() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join(MANIFEST_FILE),
+        r#"
+[[files]]
+source = "module.nix"
+destination = "nix/{{ project_name | kebab_case }}.nix"
+"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("module.nix"),
+        "{ name = \"{{ project_name }}\"; }\n",
+    )
+    .unwrap();
+
+    let reader = SourceContentReader::new(dir.path().to_str().unwrap()).unwrap();
+    let files = render_scaffold(&reader, &variables(&[("project_name", "MyApp")])).unwrap();
+    assert_eq!(files[0].path, "nix/my-app.nix");
+    assert_eq!(files[0].contents, "{ name = \"MyApp\"; }\n");
+}
+
+#[test]
+fn renders_a_single_file_by_destination_or_template_path() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join(MANIFEST_FILE),
+        "[[files]]\nsource = \"module.nix\"\ndestination = \"nix/{{ project_name }}.nix\"\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("module.nix"),
+        "{ name = \"{{ project_name }}\"; }\n",
+    )
+    .unwrap();
+
+    let reader = SourceContentReader::new(dir.path().to_str().unwrap()).unwrap();
+    let variables = variables(&[("project_name", "app")]);
+    for path in ["nix/app.nix", "module.nix"] {
+        let (file, provenance) = render_file(&reader, &variables, path).unwrap();
+        assert_eq!(file.path, "nix/app.nix");
+        assert_eq!(file.contents, "{ name = \"app\"; }\n");
+        assert_eq!(
+            provenance,
+            Provenance::Source {
+                path: "module.nix".to_string(),
+                source: reader.describe(),
+                commit: None,
+            }
+        );
+    }
+
+    // Files outside the scaffold are read from the embedded one as a last resort
+    let (file, provenance) = render_file(&reader, &variables, ".envrc").unwrap();
+    assert!(file.contents.contains("use devenv"));
+    assert_eq!(
+        provenance.to_string(),
+        format!(
+            ".envrc from the embedded scaffold, {} does not have it",
+            reader.describe()
+        )
+    );
+}
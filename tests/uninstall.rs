@@ -0,0 +1,219 @@
+mod common;
+
+use common::{rendered, scaffold_into};
+use repo_file_expander::lock::{content_hash, LockedSource, LOCK_FILE, LOCK_VERSION};
+use repo_file_expander::uninstall::{UninstallAction, UninstallPlan};
+use repo_file_expander::{FileAction, Lockfile, Variables, WritePlan};
+use std::fs;
+use std::path::Path;
+
+fn plan(target: &Path) -> UninstallPlan {
+    UninstallPlan::new(target, &Lockfile::find(target).unwrap()).unwrap()
+}
+
+#[test]
+fn removes_created_files_and_the_lockfile() {
+    let dir = tempfile::tempdir().unwrap();
+    scaffold_into(
+        dir.path(),
+        LockedSource::default(),
+        vec![
+            rendered("devenv.nix", "{ }\n"),
+            rendered("nix/shell.nix", "{ }\n"),
+        ],
+        FileAction::Overwrite,
+    );
+
+    let plan = plan(dir.path());
+    assert!(plan
+        .files()
+        .iter()
+        .all(|file| file.action == UninstallAction::Remove));
+    plan.apply(false).unwrap();
+
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn restores_backups_and_strips_managed_blocks() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("devenv.nix"), "{ mine }\n").unwrap();
+    scaffold_into(
+        dir.path(),
+        LockedSource::default(),
+        vec![rendered("devenv.nix", "{ }\n")],
+        FileAction::Backup,
+    );
+    fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+    fs::write(dir.path().join(".envrc"), "dotenv\n").unwrap();
+    fs::write(dir.path().join("Procfile"), "web: serve\n").unwrap();
+    let mut plan = WritePlan::new(
+        dir.path(),
+        vec![
+            rendered("devenv.nix", "{ }\n"),
+            rendered(".gitignore", ".devenv*\n"),
+            rendered(".envrc", "use devenv\n"),
+            rendered("Procfile", "web: serve\nworker: work\n"),
+        ],
+    )
+    .unwrap();
+    plan.resolve_conflicts(|_| Ok(FileAction::Merge)).unwrap();
+    plan.apply().unwrap();
+    let previous = Lockfile::find(dir.path()).unwrap();
+    Lockfile::record(
+        LockedSource::default(),
+        None,
+        &Variables::new(),
+        &plan,
+        Some(&previous),
+    )
+    .save(&dir.path().join(LOCK_FILE))
+    .unwrap();
+
+    let uninstall = UninstallPlan::new(dir.path(), &Lockfile::find(dir.path()).unwrap()).unwrap();
+    let actions: Vec<_> = uninstall
+        .files()
+        .iter()
+        .map(|file| (file.path.as_str(), file.action))
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            ("devenv.nix", UninstallAction::Restore),
+            (".gitignore", UninstallAction::Strip),
+            (".envrc", UninstallAction::Strip),
+            ("Procfile", UninstallAction::Keep),
+        ]
+    );
+    assert!(uninstall.files()[3].note.is_some());
+    uninstall.apply(false).unwrap();
+
+    let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+    assert_eq!(read("devenv.nix"), "{ mine }\n");
+    assert!(!dir.path().join("devenv.nix.orig").exists());
+    assert_eq!(read(".gitignore"), "target/\n");
+    assert_eq!(read(".envrc"), "dotenv\n");
+    assert_eq!(read("Procfile"), "web: serve\nworker: work\n");
+    assert!(!dir.path().join(LOCK_FILE).exists());
+}
+
+#[test]
+fn refuses_to_remove_modified_files_without_force() {
+    let dir = tempfile::tempdir().unwrap();
+    scaffold_into(
+        dir.path(),
+        LockedSource::default(),
+        vec![
+            rendered("devenv.nix", "{ }\n"),
+            rendered(".envrc", "use devenv\n"),
+        ],
+        FileAction::Overwrite,
+    );
+    fs::write(dir.path().join("devenv.nix"), "{ edited }\n").unwrap();
+
+    let plan = plan(dir.path());
+    let error = plan.apply(false).unwrap_err();
+    assert_eq!(error.exit_code(), 23);
+    assert!(error.to_string().starts_with("devenv.nix changed"));
+    assert!(dir.path().join(".envrc").exists());
+    assert!(dir.path().join(LOCK_FILE).exists());
+
+    plan.apply(true).unwrap();
+    assert!(!dir.path().join("devenv.nix").exists());
+    assert!(!dir.path().join(".envrc").exists());
+}
+
+#[test]
+fn leaves_deleted_files_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    scaffold_into(
+        dir.path(),
+        LockedSource::default(),
+        vec![rendered("devenv.nix", "{ }\n")],
+        FileAction::Overwrite,
+    );
+    fs::remove_file(dir.path().join("devenv.nix")).unwrap();
+
+    let plan = plan(dir.path());
+    assert_eq!(plan.files()[0].action, UninstallAction::Keep);
+    assert_eq!(plan.apply(false).unwrap(), [dir.path().join(LOCK_FILE)]);
+}
+
+#[test]
+fn keeps_files_the_user_had_before_init() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(".gitignore"), ".devenv*\n").unwrap();
+    scaffold_into(
+        dir.path(),
+        LockedSource::default(),
+        vec![
+            rendered(".gitignore", ".devenv*\n"),
+            rendered("devenv.nix", "{ }\n"),
+        ],
+        FileAction::Overwrite,
+    );
+
+    let plan = plan(dir.path());
+    assert_eq!(plan.files()[0].action, UninstallAction::Keep);
+    plan.apply(false).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join(".gitignore")).unwrap(),
+        ".devenv*\n"
+    );
+    assert!(!dir.path().join("devenv.nix").exists());
+}
+
+#[test]
+fn keeps_overwritten_files_without_a_backup_unless_forced() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("devenv.nix"), "{ mine }\n").unwrap();
+    scaffold_into(
+        dir.path(),
+        LockedSource::default(),
+        vec![rendered("devenv.nix", "{ }\n")],
+        FileAction::Overwrite,
+    );
+
+    let plan = plan(dir.path());
+    assert!(plan.files()[0].unrecoverable);
+    let error = plan.apply(false).unwrap_err();
+    assert_eq!(error.exit_code(), 24);
+    assert!(dir.path().join("devenv.nix").exists());
+    assert!(dir.path().join(LOCK_FILE).exists());
+
+    plan.apply(true).unwrap();
+    assert!(!dir.path().join("devenv.nix").exists());
+}
+
+#[test]
+fn refuses_created_entries_the_lockfile_cannot_vouch_for() {
+    let dir = tempfile::tempdir().unwrap();
+    scaffold_into(
+        dir.path(),
+        LockedSource::default(),
+        vec![
+            rendered("devenv.nix", "{ }\n"),
+            rendered(".envrc", "use devenv\n"),
+        ],
+        FileAction::Overwrite,
+    );
+    let mut lock = Lockfile::find(dir.path()).unwrap();
+
+    // Version 1 recorded files the user already had as created
+    lock.version = 1;
+    let plan = UninstallPlan::new(dir.path(), &lock).unwrap();
+    assert!(plan.files().iter().all(|file| file.unrecoverable));
+    assert_eq!(plan.apply(false).unwrap_err().exit_code(), 24);
+
+    // A created file that differs from its template holds merged changes
+    lock.version = LOCK_VERSION;
+    lock.files[0].sha256 = content_hash("{ merged }\n");
+    fs::write(dir.path().join("devenv.nix"), "{ merged }\n").unwrap();
+    let plan = UninstallPlan::new(dir.path(), &lock).unwrap();
+    assert!(plan.files()[0].unrecoverable);
+    assert!(!plan.files()[1].unrecoverable);
+    assert_eq!(plan.apply(false).unwrap_err().exit_code(), 24);
+    assert!(dir.path().join("devenv.nix").exists());
+    assert!(dir.path().join(".envrc").exists());
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use common::{commit_file, scaffold_template};
+use git2::Repository;
+use repo_file_expander::lock::{content_hash, LockedSource, LOCK_FILE};
+use repo_file_expander::status::{FileState, ProjectStatus};
+use repo_file_expander::update::{UpdateOutcome, UpdatePlan};
+use repo_file_expander::{
+    render_scaffold, FileAction, Lockfile, SourceContentReader, SourceOptions, Variables,
+};
+use std::fs;
+use std::path::Path;
+
+const MANIFEST: &str = "[[files]]\nsource = \"devenv.nix\"\n\n[[files]]\nsource = \".gitignore\"\n";
+
+const NIX_V1: &str = "{ pkgs, ... }: {\n  packages = [ pkgs.git ];\n\n  enterShell = ''\n    git --version\n  '';\n}\n";
+
+/// A template repository with `NIX_V1` and a project scaffolded from it
+fn scaffold(gitignore: &str) -> (tempfile::TempDir, Repository, tempfile::TempDir) {
+    scaffold_template(
+        &[
+            ("rfe.toml", MANIFEST),
+            (".gitignore", gitignore),
+            ("devenv.nix", NIX_V1),
+        ],
+        &[(".gitignore", "target/\n")],
+        FileAction::Merge,
+    )
+}
+
+fn open(path: &Path) -> SourceContentReader {
+    SourceContentReader::open(path.to_str().unwrap(), &SourceOptions::default()).unwrap()
+}
+
+/// Plan updating `project` to the template's current commit, as `rfe update` does
+fn plan_update(project: &Path, reject: bool) -> UpdatePlan {
+    let lock = Lockfile::find(project).unwrap();
+    let next = render_scaffold(
+        &lock.source.open(None, &SourceOptions::default()).unwrap(),
+        &lock.variables(),
+    )
+    .unwrap();
+    let base_reader = lock
+        .source
+        .open(lock.source.commit.as_deref(), &SourceOptions::default())
+        .unwrap();
+    let base = render_scaffold(&base_reader, &lock.variables()).unwrap();
+    UpdatePlan::new(project, &lock, Some(&base), next, reject).unwrap()
+}
+
+/// Record the applied `plan` as `rfe update` does
+fn save_lock(plan: &UpdatePlan, repo: &Repository) {
+    let reader = open(repo.workdir().unwrap());
+    plan.lockfile(LockedSource::of(&reader), None, &Variables::new())
+        .save(&plan.target().join(LOCK_FILE))
+        .unwrap();
+}
+
+fn outcome(plan: &UpdatePlan, path: &str) -> UpdateOutcome {
+    plan.files()
+        .iter()
+        .find(|f| f.path == path)
+        .unwrap()
+        .outcome
+}
+
+#[test]
+fn takes_template_changes_for_unmodified_files() {
+    let (_template, repo, project) = scaffold(".devenv*\n");
+    commit_file(
+        &repo,
+        "devenv.nix",
+        &NIX_V1.replace("pkgs.git", "pkgs.git pkgs.jq"),
+    );
+
+    let plan = plan_update(project.path(), false);
+    assert_eq!(outcome(&plan, "devenv.nix"), UpdateOutcome::Updated);
+    assert_eq!(outcome(&plan, ".gitignore"), UpdateOutcome::Unchanged);
+    plan.apply().unwrap();
+
+    assert!(fs::read_to_string(project.path().join("devenv.nix"))
+        .unwrap()
+        .contains("pkgs.git pkgs.jq"));
+}
+
+#[test]
+fn merges_local_and_template_changes() {
+    let (_template, repo, project) = scaffold(".devenv*\n");
+    let nix = project.path().join("devenv.nix");
+    fs::write(
+        &nix,
+        NIX_V1.replace("git --version", "git --version\n    cargo --version"),
+    )
+    .unwrap();
+    commit_file(
+        &repo,
+        "devenv.nix",
+        &NIX_V1.replace("pkgs.git", "pkgs.git pkgs.jq"),
+    );
+    commit_file(&repo, ".gitignore", ".devenv*\n.direnv\n");
+
+    let plan = plan_update(project.path(), false);
+    assert_eq!(outcome(&plan, "devenv.nix"), UpdateOutcome::Merged);
+    assert_eq!(outcome(&plan, ".gitignore"), UpdateOutcome::Merged);
+    plan.apply().unwrap();
+
+    let merged = fs::read_to_string(&nix).unwrap();
+    assert!(merged.contains("pkgs.git pkgs.jq"));
+    assert!(merged.contains("cargo --version"));
+    assert_eq!(
+        fs::read_to_string(project.path().join(".gitignore")).unwrap(),
+        "target/\n\n# rfe-managed begin\n.devenv*\n.direnv\n# rfe-managed end\n"
+    );
+}
+
+#[test]
+fn marks_conflicting_changes() {
+    let (_template, repo, project) = scaffold(".devenv*\n");
+    let nix = project.path().join("devenv.nix");
+    fs::write(&nix, NIX_V1.replace("pkgs.git", "pkgs.git pkgs.curl")).unwrap();
+    commit_file(
+        &repo,
+        "devenv.nix",
+        &NIX_V1.replace("pkgs.git", "pkgs.git pkgs.jq"),
+    );
+
+    let plan = plan_update(project.path(), false);
+    assert_eq!(outcome(&plan, "devenv.nix"), UpdateOutcome::Conflicted);
+    plan.apply().unwrap();
+
+    let conflicted = fs::read_to_string(&nix).unwrap();
+    assert!(conflicted.contains(
+        "<<<<<<< existing\n  packages = [ pkgs.git pkgs.curl ];\n=======\n  packages = [ pkgs.git pkgs.jq ];\n>>>>>>> template\n"
+    ));
+    assert_eq!(plan.unresolved(), ["devenv.nix"]);
+    save_lock(&plan, &repo);
+
+    // The conflict is still reported until the markers are gone
+    let lock = Lockfile::find(project.path()).unwrap();
+    assert_eq!(
+        lock.file("devenv.nix").unwrap().template_sha256,
+        content_hash(NIX_V1)
+    );
+    let plan = plan_update(project.path(), false);
+    assert_eq!(outcome(&plan, "devenv.nix"), UpdateOutcome::Conflicted);
+    plan.apply().unwrap();
+    assert_eq!(fs::read_to_string(&nix).unwrap(), conflicted);
+}
+
+#[test]
+fn writes_rejects_instead_of_markers() {
+    let (_template, repo, project) = scaffold(".devenv*\n");
+    let first = Lockfile::find(project.path()).unwrap().source.commit;
+    let nix = project.path().join("devenv.nix");
+    let local = NIX_V1.replace("pkgs.git", "pkgs.git pkgs.curl");
+    fs::write(&nix, &local).unwrap();
+    commit_file(
+        &repo,
+        "devenv.nix",
+        &NIX_V1.replace("pkgs.git", "pkgs.git pkgs.jq"),
+    );
+
+    let plan = plan_update(project.path(), true);
+    assert_eq!(outcome(&plan, "devenv.nix"), UpdateOutcome::Rejected);
+    plan.apply().unwrap();
+
+    assert_eq!(fs::read_to_string(&nix).unwrap(), local);
+    let reject = fs::read_to_string(project.path().join("devenv.nix.rej")).unwrap();
+    assert!(reject.contains("-  packages = [ pkgs.git ];\n+  packages = [ pkgs.git pkgs.jq ];\n"));
+    save_lock(&plan, &repo);
+
+    // The file keeps its old lock entry while the .rej file is there
+    let before = Lockfile::find(project.path()).unwrap();
+    let plan = plan_update(project.path(), false);
+    assert_eq!(outcome(&plan, "devenv.nix"), UpdateOutcome::Rejected);
+    assert_eq!(plan.unresolved(), ["devenv.nix"]);
+    plan.apply().unwrap();
+    assert_eq!(fs::read_to_string(&nix).unwrap(), local);
+    let after = plan.lockfile(LockedSource::default(), None, &Variables::new());
+    assert_eq!(after.file("devenv.nix"), before.file("devenv.nix"));
+
+    // The lockfile stays at the old commit, so deleting the .rej file does
+    // not drop the template change
+    assert_eq!(before.source.commit, first);
+    fs::remove_file(project.path().join("devenv.nix.rej")).unwrap();
+    let plan = plan_update(project.path(), false);
+    assert_eq!(outcome(&plan, "devenv.nix"), UpdateOutcome::Conflicted);
+}
+
+#[test]
+fn records_the_new_commit() {
+    let (_template, repo, project) = scaffold(".devenv*\n");
+    let head = commit_file(&repo, "devenv.nix", &NIX_V1.replace("pkgs.git", "pkgs.jq"));
+
+    let plan = plan_update(project.path(), false);
+    plan.apply().unwrap();
+    let reader = open(repo.workdir().unwrap());
+    let lock = plan.lockfile(LockedSource::of(&reader), None, &Variables::new());
+    assert_eq!(lock.source.commit, Some(head.to_string()));
+    assert_eq!(lock.file(".gitignore").unwrap().action, FileAction::Merge);
+    lock.save(&project.path().join(LOCK_FILE)).unwrap();
+
+    // A second update has nothing left to do
+    let plan = plan_update(project.path(), false);
+    assert!(plan
+        .files()
+        .iter()
+        .all(|file| file.outcome == UpdateOutcome::Unchanged));
+}
+
+#[test]
+fn files_already_matching_the_new_template_are_clean_afterwards() {
+    let (_template, repo, project) = scaffold(".devenv*\n");
+    let v2 = NIX_V1.replace("pkgs.git", "pkgs.git pkgs.jq");
+    commit_file(&repo, "devenv.nix", &v2);
+    fs::write(project.path().join("devenv.nix"), &v2).unwrap();
+
+    let plan = plan_update(project.path(), false);
+    assert_eq!(outcome(&plan, "devenv.nix"), UpdateOutcome::Unchanged);
+    plan.apply().unwrap();
+    save_lock(&plan, &repo);
+
+    let lock = Lockfile::find(project.path()).unwrap();
+    let next = render_scaffold(&open(repo.workdir().unwrap()), &Variables::new()).unwrap();
+    let status = ProjectStatus::new(project.path(), &lock, &next).unwrap();
+    let nix = status
+        .files()
+        .iter()
+        .find(|file| file.path == "devenv.nix")
+        .unwrap();
+    assert_eq!(nix.state, FileState::Unmodified);
+    assert!(!nix.template_changed);
+}